@@ -4,3 +4,32 @@ void print_int(int x) {
     printf("%d\n", x);
     fflush(stdout);
 }
+
+// print_int already ends the line, this name makes it explicit in programs
+void println_int(int x) {
+    print_int(x);
+}
+
+void print_int_hex(int x) {
+    printf("0x%x\n", (unsigned int)x);
+    fflush(stdout);
+}
+
+// negative values are printed as their 32-bit two's complement, like in hex
+void print_int_bin(int x) {
+    unsigned int value = (unsigned int)x;
+    char digits[33];
+    int length = 0;
+
+    do {
+        digits[length++] = '0' + (value & 1);
+        value >>= 1;
+    } while (value != 0);
+
+    printf("0b");
+    while (length > 0) {
+        putchar(digits[--length]);
+    }
+    printf("\n");
+    fflush(stdout);
+}
@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+use crate::ast::Operator;
+
+// decides what happens when integer arithmetic overflows, for the whole program.
+// the same mode is used when folding constants at compile time and when emitting
+// the instructions that compute the value at runtime. variables live in 32-bit slots,
+// so folding uses i32 arithmetic too and both agree, except for i32::MIN / -1, which
+// idiv faults on at runtime in every mode
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ArithmeticMode {
+    // two's complement wrap around, like plain machine instructions
+    #[default]
+    Wrap,
+    // overflow is an error
+    Check,
+    // the result is clamped to the i32 range
+    Saturate,
+}
+
+impl FromStr for ArithmeticMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "wrap" => Ok(Self::Wrap),
+            "check" => Ok(Self::Check),
+            "saturate" => Ok(Self::Saturate),
+            _ => Err(format!(
+                "Unknown arithmetic mode {}, expected one of wrap, check, saturate",
+                value
+            )),
+        }
+    }
+}
+
+impl ArithmeticMode {
+    pub fn fold_binary(&self, left: i64, operator: &Operator, right: i64) -> i64 {
+        let (left, right) = (left as i32, right as i32);
+
+        if matches!(operator, Operator::Divide | Operator::Modulo) && right == 0 {
+            panic!(
+                "Division by zero while evaluating {} {} {}",
+                left, operator, right
+            );
+        }
+
+        let result = match (self, operator) {
+            // comparisons cannot overflow, so they do not depend on the mode
+            (_, Operator::Equal) => Some((left == right) as i32),
+            (_, Operator::NotEqual) => Some((left != right) as i32),
+            (_, Operator::Less) => Some((left < right) as i32),
+            (_, Operator::LessEqual) => Some((left <= right) as i32),
+            (_, Operator::Greater) => Some((left > right) as i32),
+            (_, Operator::GreaterEqual) => Some((left >= right) as i32),
+            (Self::Wrap, Operator::Add) => Some(left.wrapping_add(right)),
+            (Self::Wrap, Operator::Sub) => Some(left.wrapping_sub(right)),
+            (Self::Wrap, Operator::Multiply) => Some(left.wrapping_mul(right)),
+            (Self::Wrap, Operator::Divide) => Some(left.wrapping_div(right)),
+            (Self::Wrap, Operator::Modulo) => Some(left.wrapping_rem(right)),
+            (Self::Check, Operator::Add) => left.checked_add(right),
+            (Self::Check, Operator::Sub) => left.checked_sub(right),
+            (Self::Check, Operator::Multiply) => left.checked_mul(right),
+            (Self::Check, Operator::Divide) => left.checked_div(right),
+            (Self::Check, Operator::Modulo) => left.checked_rem(right),
+            (Self::Saturate, Operator::Add) => Some(left.saturating_add(right)),
+            (Self::Saturate, Operator::Sub) => Some(left.saturating_sub(right)),
+            (Self::Saturate, Operator::Multiply) => Some(left.saturating_mul(right)),
+            (Self::Saturate, Operator::Divide) => Some(left.saturating_div(right)),
+            // the only overflowing remainder, i32::MIN % -1, is 0 in exact arithmetic
+            (Self::Saturate, Operator::Modulo) => Some(left.wrapping_rem(right)),
+        };
+
+        let result = result.unwrap_or_else(|| {
+            panic!(
+                "Arithmetic overflow while evaluating {} {} {}",
+                left, operator, right
+            )
+        });
+
+        i64::from(result)
+    }
+
+    pub fn fold_negation(&self, value: i64) -> i64 {
+        let value = value as i32;
+
+        let result = match self {
+            Self::Wrap => value.wrapping_neg(),
+            Self::Check => value
+                .checked_neg()
+                .unwrap_or_else(|| panic!("Arithmetic overflow while negating {}", value)),
+            Self::Saturate => value.saturating_neg(),
+        };
+
+        i64::from(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_mode_from_flag_value() {
+        // given
+        let values = ["wrap", "check", "saturate", "explode"];
+
+        // when
+        let result: Vec<Result<ArithmeticMode, String>> =
+            values.iter().map(|value| value.parse()).collect();
+
+        // then
+        assert_eq!(
+            vec![
+                Ok(ArithmeticMode::Wrap),
+                Ok(ArithmeticMode::Check),
+                Ok(ArithmeticMode::Saturate),
+                Err(
+                    "Unknown arithmetic mode explode, expected one of wrap, check, saturate"
+                        .to_string()
+                ),
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn fold_with_the_32_bit_range_of_variables() {
+        // given
+        let modes = [ArithmeticMode::Wrap, ArithmeticMode::Saturate];
+
+        // when
+        let result: Vec<i64> = modes
+            .iter()
+            .map(|mode| mode.fold_binary(-65536, &Operator::Multiply, 65537))
+            .collect();
+
+        // then
+        assert_eq!(vec![-65536, i64::from(i32::MIN)], result);
+    }
+}
@@ -1,10 +1,14 @@
+use std::fmt;
+
 use crate::tokenizer::TokenType;
 
 pub type Program = Module;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Default)]
 pub struct Module {
     pub statements: Vec<Statement>,
+    // a final expression without a semicolon, its value becomes the exit code of the program
+    pub tail: Option<Expression>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -33,9 +37,72 @@ pub enum Expression {
     },
     VariableAccess {
         name: String,
+        // (line, column) of the access in the source, (0, 0) for compiler generated accesses
+        location: (usize, usize),
     },
     Grouping {
         expression: Box<Expression>
+    },
+    Cast {
+        expression: Box<Expression>,
+        target: Type,
+    },
+    // escape sequences are already applied by the tokenizer
+    StringLiteral {
+        value: String,
+    },
+    // stored as 1 or 0, like the result of a comparison
+    Boolean {
+        value: bool,
+    },
+}
+
+impl Expression {
+    // calls are the only expressions with side effects
+    pub fn contains_call(&self) -> bool {
+        match self {
+            Self::Call { .. } => true,
+            Self::Constant { .. }
+            | Self::VariableAccess { .. }
+            | Self::StringLiteral { .. }
+            | Self::Boolean { .. } => false,
+            Self::UnaryOp { operand, .. } => operand.contains_call(),
+            Self::BinaryOp { left, right, .. } => left.contains_call() || right.contains_call(),
+            Self::Grouping { expression } => expression.contains_call(),
+            Self::Cast { expression, .. } => expression.contains_call(),
+        }
+    }
+
+    pub fn calls(&self, function: &str) -> bool {
+        match self {
+            Self::Call { name, args } => {
+                name == function || args.iter().any(|arg| arg.calls(function))
+            }
+            Self::Constant { .. }
+            | Self::VariableAccess { .. }
+            | Self::StringLiteral { .. }
+            | Self::Boolean { .. } => false,
+            Self::UnaryOp { operand, .. } => operand.calls(function),
+            Self::BinaryOp { left, right, .. } => left.calls(function) || right.calls(function),
+            Self::Grouping { expression } => expression.calls(function),
+            Self::Cast { expression, .. } => expression.calls(function),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Type {
+    I32,
+    I64,
+}
+
+impl Type {
+    pub fn try_match_from_name(name: &str) -> Option<Type> {
+        match name {
+            "i32" => Some(Type::I32),
+            "i64" => Some(Type::I64),
+            _ => None,
+        }
     }
 }
 
@@ -44,7 +111,40 @@ pub enum Operator {
     Sub,
     Add,
     Multiply,
-    Divide
+    Divide,
+    // the remainder has the sign of the dividend, like idiv computes it
+    Modulo,
+    // comparisons evaluate to 1 when they hold and to 0 otherwise
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Operator {
+    #[cfg(test)]
+    pub fn is_commutative(&self) -> bool {
+        matches!(
+            self,
+            Self::Add | Self::Multiply | Self::Equal | Self::NotEqual
+        )
+    }
+
+    // binary operators with a higher precedence bind tighter, so 1 + 2 * 3 is 1 + (2 * 3)
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Self::Equal
+            | Self::NotEqual
+            | Self::Less
+            | Self::LessEqual
+            | Self::Greater
+            | Self::GreaterEqual => 1,
+            Self::Add | Self::Sub => 2,
+            Self::Multiply | Self::Divide | Self::Modulo => 3,
+        }
+    }
 }
 
 impl From<&TokenType> for Operator {
@@ -54,7 +154,390 @@ impl From<&TokenType> for Operator {
             TokenType::Minus => Self::Sub,
             TokenType::Star => Self::Multiply,
             TokenType::Slash => Self::Divide,
+            TokenType::Percent => Self::Modulo,
+            TokenType::EqualsEquals => Self::Equal,
+            TokenType::NotEquals => Self::NotEqual,
+            TokenType::LessThan => Self::Less,
+            TokenType::LessThanEquals => Self::LessEqual,
+            TokenType::GreaterThan => Self::Greater,
+            TokenType::GreaterThanEquals => Self::GreaterEqual,
             _ => panic!("Unknown operator for TokenType: {}", value),
         }
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::I32 => write!(f, "i32"),
+            Self::I64 => write!(f, "i64"),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Self::Sub => "-",
+            Self::Add => "+",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Modulo => "%",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+// expressions are written back the way the parser reads them, parentheses from the source
+// are kept as groupings and are only added where the tree would otherwise parse differently
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Constant { value } => write!(f, "{}", value),
+            Self::UnaryOp { operator, operand } => {
+                let operand = match operand.as_ref() {
+                    Self::BinaryOp { .. } | Self::Cast { .. } => format!("({})", operand),
+                    _ => operand.to_string(),
+                };
+                // a space keeps - -x from being read as a negative literal
+                let separator = if operand.starts_with(['-', '+']) {
+                    " "
+                } else {
+                    ""
+                };
+
+                write!(f, "{}{}{}", operator, separator, operand)
+            }
+            Self::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                // operators of the same precedence lean to the left, so only the right side
+                // needs parentheses for them
+                let needs_parentheses = |operand: &Expression, is_right: bool| match operand {
+                    Self::BinaryOp {
+                        operator: inner, ..
+                    } => {
+                        inner.precedence() < operator.precedence()
+                            || (is_right && inner.precedence() == operator.precedence())
+                    }
+                    _ => false,
+                };
+                let wrap = |operand: &Expression, is_right: bool| {
+                    if needs_parentheses(operand, is_right) {
+                        format!("({})", operand)
+                    } else {
+                        operand.to_string()
+                    }
+                };
+
+                write!(
+                    f,
+                    "{} {} {}",
+                    wrap(left, false),
+                    operator,
+                    wrap(right, true)
+                )
+            }
+            Self::Call { name, args } => {
+                let args: Vec<String> = args.iter().map(Expression::to_string).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Self::VariableAccess { name, .. } => write!(f, "{}", name),
+            Self::Grouping { expression } => write!(f, "({})", expression),
+            Self::Cast { expression, target } => match expression.as_ref() {
+                Self::BinaryOp { .. } => write!(f, "({}) as {}", expression, target),
+                _ => write!(f, "{} as {}", expression, target),
+            },
+            Self::StringLiteral { value } => {
+                // the value has its escape sequences applied, they are written back as in the source
+                let escaped: String = value
+                    .chars()
+                    .map(|c| match c {
+                        '\\' => "\\\\".to_string(),
+                        '"' => "\\\"".to_string(),
+                        '\n' => "\\n".to_string(),
+                        '\r' => "\\r".to_string(),
+                        '\t' => "\\t".to_string(),
+                        c if c.is_ascii_control() => format!("\\x{:02X}", c as u8),
+                        c => c.to_string(),
+                    })
+                    .collect();
+                write!(f, "\"{}\"", escaped)
+            }
+            Self::Boolean { value } => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Expression(expression) => write!(f, "{};", expression),
+            Self::VariableDeclaration { name, value } => write!(f, "let {} = {};", name, value),
+        }
+    }
+}
+
+// renders the program as canonical source, one statement per line
+pub fn format_program(program: &Program) -> String {
+    let mut source = String::new();
+
+    for statement in &program.statements {
+        source.push_str(&format!("{}\n", statement));
+    }
+
+    if let Some(tail) = &program.tail {
+        source.push_str(&format!("{}\n", tail));
+    }
+
+    source
+}
+
+// structural equality for tests, source locations are not compared
+#[cfg(test)]
+pub fn ast_eq_ignoring_spans(a: &Program, b: &Program) -> bool {
+    without_spans(a) == without_spans(b)
+}
+
+// the program with every source location zeroed, tests compare these with assert_eq! to get a
+// diff of the structure
+#[cfg(test)]
+pub fn without_spans(program: &Program) -> Program {
+    let statements = program
+        .statements
+        .iter()
+        .map(|statement| match statement {
+            Statement::Expression(expression) => {
+                Statement::Expression(expression_without_spans(expression))
+            }
+            Statement::VariableDeclaration { name, value } => Statement::VariableDeclaration {
+                name: name.clone(),
+                value: expression_without_spans(value),
+            },
+        })
+        .collect();
+
+    Program {
+        statements,
+        tail: program.tail.as_ref().map(expression_without_spans),
+    }
+}
+
+#[cfg(test)]
+fn expression_without_spans(expression: &Expression) -> Expression {
+    match expression {
+        Expression::Constant { .. }
+        | Expression::StringLiteral { .. }
+        | Expression::Boolean { .. } => expression.clone(),
+        Expression::UnaryOp { operator, operand } => Expression::UnaryOp {
+            operator: operator.clone(),
+            operand: Box::new(expression_without_spans(operand)),
+        },
+        Expression::BinaryOp {
+            left,
+            operator,
+            right,
+        } => Expression::BinaryOp {
+            left: Box::new(expression_without_spans(left)),
+            operator: operator.clone(),
+            right: Box::new(expression_without_spans(right)),
+        },
+        Expression::Call { name, args } => Expression::Call {
+            name: name.clone(),
+            args: args.iter().map(expression_without_spans).collect(),
+        },
+        Expression::VariableAccess { name, .. } => Expression::VariableAccess {
+            name: name.clone(),
+            location: (0, 0),
+        },
+        Expression::Grouping { expression } => Expression::Grouping {
+            expression: Box::new(expression_without_spans(expression)),
+        },
+        Expression::Cast { expression, target } => Expression::Cast {
+            expression: Box::new(expression_without_spans(expression)),
+            target: target.clone(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::{parser::Parser, tokenizer::Tokenizer};
+
+    use super::*;
+
+    #[test]
+    fn only_add_and_multiply_are_commutative() {
+        // given
+        let operators = [
+            Operator::Add,
+            Operator::Sub,
+            Operator::Multiply,
+            Operator::Divide,
+        ];
+
+        // when
+        let result: Vec<bool> = operators.iter().map(Operator::is_commutative).collect();
+
+        // then
+        assert_eq!(vec![true, false, true, false], result);
+    }
+
+    #[test]
+    fn parse_operators_according_to_precedence() {
+        // given
+        let tokens = Tokenizer::new("1 - 2 * 3 + 4 / 5;").tokenize();
+
+        // when
+        let program = Parser::new(tokens).parse();
+
+        // then
+        assert!(Operator::Multiply.precedence() > Operator::Add.precedence());
+        assert_eq!(Operator::Add.precedence(), Operator::Sub.precedence());
+        assert_eq!(
+            Operator::Multiply.precedence(),
+            Operator::Divide.precedence()
+        );
+        assert_eq!(
+            vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::BinaryOp {
+                    left: Box::new(Expression::Constant { value: 1 }),
+                    operator: Operator::Sub,
+                    right: Box::new(Expression::BinaryOp {
+                        left: Box::new(Expression::Constant { value: 2 }),
+                        operator: Operator::Multiply,
+                        right: Box::new(Expression::Constant { value: 3 }),
+                    }),
+                }),
+                operator: Operator::Add,
+                right: Box::new(Expression::BinaryOp {
+                    left: Box::new(Expression::Constant { value: 4 }),
+                    operator: Operator::Divide,
+                    right: Box::new(Expression::Constant { value: 5 }),
+                }),
+            })],
+            program.statements
+        );
+    }
+
+    #[test]
+    fn compare_programs_differing_only_in_spans_as_equal() {
+        // given
+        let program = |location| Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::VariableAccess {
+                    name: "a".to_string(),
+                    location,
+                }],
+            })],
+            tail: None,
+        };
+        let parsed = program((3, 10));
+        let constructed = program((0, 0));
+
+        // when
+        let result = ast_eq_ignoring_spans(&parsed, &constructed);
+
+        // then
+        assert_ne!(parsed, constructed);
+        assert!(result);
+    }
+
+    #[test]
+    fn display_operators_as_source_symbols() {
+        // given
+        let operators = [
+            Operator::Add,
+            Operator::Sub,
+            Operator::Multiply,
+            Operator::Divide,
+            Operator::Modulo,
+            Operator::Equal,
+            Operator::NotEqual,
+            Operator::Less,
+            Operator::LessEqual,
+            Operator::Greater,
+            Operator::GreaterEqual,
+        ];
+
+        // when
+        let result: Vec<String> = operators.iter().map(Operator::to_string).collect();
+
+        // then
+        assert_eq!(
+            vec!["+", "-", "*", "/", "%", "==", "!=", "<", "<=", ">", ">="],
+            result
+        );
+    }
+
+    #[test]
+    fn format_program_that_parses_back_to_the_same_ast() {
+        // given
+        let source = "let   a,b=-(1+2)*3 as i64;print_int( a ,\"x\\n\" );\n\
+                      let c = - -a - (b - 1) + len(\"ab\") as i32;\n\
+                      a<=c";
+        let program = Parser::new(Tokenizer::new(source).tokenize()).parse();
+
+        // when
+        let formatted = format_program(&program);
+        let reparsed = Parser::new(Tokenizer::new(&formatted).tokenize()).parse();
+
+        // then
+        assert_eq!(
+            "let a = -(1 + 2) * 3 as i64;\n\
+             let b = a;\n\
+             print_int(a, \"x\\n\");\n\
+             let c = - -a - (b - 1) + len(\"ab\") as i32;\n\
+             a <= c\n",
+            formatted
+        );
+        assert_eq!(without_spans(&program), without_spans(&reparsed));
+    }
+
+    #[test]
+    fn format_control_characters_in_strings_as_escapes() {
+        // given
+        let source = r#"print_int(len("a\x01b\x0Dc\0\x7F"));"#;
+        let program = Parser::new(Tokenizer::new(source).tokenize()).parse();
+
+        // when
+        let formatted = format_program(&program);
+        let reparsed = Parser::new(Tokenizer::new(&formatted).tokenize()).parse();
+
+        // then
+        assert_eq!("print_int(len(\"a\\x01b\\rc\\x00\\x7F\"));\n", formatted);
+        assert_eq!(without_spans(&program), without_spans(&reparsed));
+    }
+
+    #[test]
+    fn format_parentheses_required_by_the_tree() {
+        // given
+        let expression = Expression::BinaryOp {
+            left: Box::new(Expression::Constant { value: 8 }),
+            operator: Operator::Sub,
+            right: Box::new(Expression::BinaryOp {
+                left: Box::new(Expression::Constant { value: 2 }),
+                operator: Operator::Add,
+                right: Box::new(Expression::Constant { value: 1 }),
+            }),
+        };
+
+        // when
+        let result = expression.to_string();
+
+        // then
+        assert_eq!("8 - (2 + 1)", result);
+    }
+}
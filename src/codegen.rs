@@ -1,105 +1,611 @@
 use core::panic;
-use std::{collections::HashMap, vec};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    ops::Range,
+    vec,
+};
 
-use crate::ast::{Expression, Program, Statement};
+use crate::{
+    arithmetic::ArithmeticMode,
+    ast::{Expression, Operator, Program, Statement, Type},
+    partial_evaluator::MIN_MAX_INTRINSICS,
+};
 
-type Instruction = String;
+pub type Instruction = String;
+
+// integer arguments are passed in these registers, in order, by the System V calling convention
+const ARGUMENT_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+
+// functions implemented in runtime.c, linked into every program
+const RUNTIME_FUNCTIONS: [&str; 2] = ["print_int", "println_int"];
+
+// in check mode, an overflowing add, sub, imul or neg jumps here and the program stops
+const OVERFLOW_TRAP_LABEL: &str = "arithmetic_overflow";
+
+// also implemented in runtime.c, but declared only when the program calls them
+const OPTIONAL_RUNTIME_FUNCTIONS: [&str; 2] = ["print_int_hex", "print_int_bin"];
+
+// variadic functions from the C library, declared only when the program calls them
+const VARIADIC_LIBC_FUNCTIONS: [&str; 1] = ["printf"];
+
+// bytes below rsp that leaf functions can use without moving rsp, as guaranteed by System V
+const RED_ZONE_SIZE: u32 = 128;
+
+// the default stack size limit on linux, a bigger frame would crash the program anyway
+const MAX_STACK_SIZE: u32 = 8 * 1024 * 1024;
+
+// a summary of the generated code, to compare the output before and after changing a pass
+#[derive(Debug, PartialEq)]
+pub struct CodegenStats {
+    pub instruction_count: usize,
+    pub stack_bytes: u32,
+    pub variable_count: usize,
+    pub extern_count: usize,
+}
+
+impl fmt::Display for CodegenStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "instructions: {}, stack bytes: {}, variables: {}, externs: {}",
+            self.instruction_count, self.stack_bytes, self.variable_count, self.extern_count
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CodegenError {
+    StackTooLarge,
+    // found by verify_instructions, index is the position of the offending instruction
+    UnknownCallTarget {
+        index: usize,
+        instruction: Instruction,
+    },
+    UnknownJumpTarget {
+        index: usize,
+        instruction: Instruction,
+    },
+    RetWithoutPrologue {
+        index: usize,
+    },
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::StackTooLarge => write!(
+                f,
+                "Program variables need more than {} bytes of stack",
+                MAX_STACK_SIZE
+            ),
+            Self::UnknownCallTarget { index, instruction } => write!(
+                f,
+                "Call to a function that is neither extern nor defined at instruction {}: {}",
+                index, instruction
+            ),
+            Self::UnknownJumpTarget { index, instruction } => write!(
+                f,
+                "Jump to an undefined label at instruction {}: {}",
+                index, instruction
+            ),
+            Self::RetWithoutPrologue { index } => write!(
+                f,
+                "Return without a matching function prologue at instruction {}",
+                index
+            ),
+        }
+    }
+}
 
 pub struct X86AssemblyCodegen {
     program: Program,
+    options: CodegenOptions,
     environment: Environment,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct CodegenOptions {
+    // skip allocating stack space when the program makes no calls and fits in the red zone
+    pub use_red_zone: bool,
+    // put a .stmt_N label before every top level statement, so profilers can attribute samples
+    pub emit_statement_labels: bool,
+    // a variable declared once with a constant initializer is emitted to .data instead of
+    // being stored on the stack
+    pub constant_globals: bool,
+    // variables are addressed relative to rsp, which frees rbp from setting up a frame
+    pub omit_frame_pointer: bool,
+    // start the listing with a comment naming the compiler that produced it
+    pub emit_ident: bool,
+    // with indirect branch tracking enforced, a function reached through an indirect call
+    // has to start with endbr64, main is called by libc through a pointer
+    pub emit_endbranch: bool,
+    // what an overflowing add, sub, imul or neg does at runtime
+    pub arithmetic_mode: ArithmeticMode,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self {
+            use_red_zone: true,
+            emit_statement_labels: false,
+            constant_globals: false,
+            omit_frame_pointer: false,
+            emit_ident: true,
+            emit_endbranch: false,
+            arithmetic_mode: ArithmeticMode::default(),
+        }
+    }
+}
+
 #[derive(Default)]
 struct Environment {
     // variable name to stack offset map
     allocated_variables: HashMap<String, u32>,
     stack_offset: u32,
+    // the aligned space reserved for stack variables, whether rsp is moved or not
+    stack_size: u32,
+    // emitted to .rodata, the index is part of the label
+    string_literals: Vec<String>,
+    // functions declared extern only because the program calls them
+    called_extern_functions: BTreeSet<String>,
+    // variables living in .data, with their initial value
+    global_variables: Vec<(String, i64)>,
+    omit_frame_pointer: bool,
+    // without a frame pointer, variables are found from how far rsp moved since main began,
+    // which is the stack allocation plus anything pushed for the call being emitted
+    frame_size: u32,
+    pushed_bytes: u32,
 }
 
 impl Environment {
     fn allocate_variable(&mut self, name: String) {
         // TODO: support different size of variables
         // TODO: error handling
-        self.stack_offset = self.stack_offset + 4;
+        self.stack_offset += 4;
         self.allocated_variables.insert(name, self.stack_offset);
     }
 
     fn get_variable_stack_offset(&self, name: &String) -> u32 {
-        // TODO: error handling
-        *self.allocated_variables.get(name).unwrap()
+        *self
+            .allocated_variables
+            .get(name)
+            .unwrap_or_else(|| panic!("Variable {} is used before it is declared", name))
+    }
+
+    fn is_global_variable(&self, name: &String) -> bool {
+        self.global_variables
+            .iter()
+            .any(|(global, _)| global == name)
+    }
+
+    // the memory operand holding the variable, on the stack or at its .data label
+    fn variable_operand(&self, name: &String) -> String {
+        if self.is_global_variable(name) {
+            format!("dword [rel {}]", Self::global_label(name))
+        } else {
+            self.stack_variable_operand(self.get_variable_stack_offset(name))
+        }
+    }
+
+    // stack offsets count down from where rbp would point, the return address is above it
+    fn stack_variable_operand(&self, stack_offset: u32) -> String {
+        if !self.omit_frame_pointer {
+            return format!("dword [rbp - {}]", stack_offset);
+        }
+
+        let rsp_offset =
+            i64::from(self.frame_size) + i64::from(self.pushed_bytes) - i64::from(stack_offset);
+        if rsp_offset < 0 {
+            format!("dword [rsp - {}]", -rsp_offset)
+        } else {
+            format!("dword [rsp + {}]", rsp_offset)
+        }
+    }
+
+    // the prefix keeps variables from clashing with main or the string literal labels
+    fn global_label(name: &str) -> String {
+        format!("global_{}", name)
+    }
+
+    fn add_string_literal(&mut self, value: &str) -> String {
+        self.string_literals.push(value.to_string());
+
+        format!("str_{}", self.string_literals.len() - 1)
+    }
+}
+
+// every label defined in the instructions, in order of appearance, addresses are only
+// known after linking, so the order is all a map file can tell
+pub fn collect_labels(instructions: &[Instruction]) -> Vec<String> {
+    instructions
+        .iter()
+        .filter_map(|instruction| instruction.split_once(':'))
+        .map(|(label, _)| label)
+        .filter(|label| !label.contains(' '))
+        .map(String::from)
+        .collect()
+}
+
+// catches codegen mistakes before nasm reports them without any context, a ret has to be
+// inside a function and after the pop rbp matching its push rbp
+pub fn verify_instructions(instructions: &[Instruction]) -> Result<(), CodegenError> {
+    let labels = collect_labels(instructions);
+    let externs: Vec<&str> = instructions
+        .iter()
+        .filter_map(|instruction| instruction.strip_prefix("extern "))
+        .collect();
+
+    let mut is_in_function = false;
+    let mut is_frame_pointer_pushed = false;
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let (mnemonic, operand) = instruction
+            .split_once(' ')
+            .unwrap_or((instruction.as_str(), ""));
+
+        match mnemonic {
+            "call" if !externs.contains(&operand) && !labels.iter().any(|l| l == operand) => {
+                return Err(CodegenError::UnknownCallTarget {
+                    index,
+                    instruction: instruction.clone(),
+                });
+            }
+            _ if mnemonic.starts_with('j') && !labels.iter().any(|l| l == operand) => {
+                return Err(CodegenError::UnknownJumpTarget {
+                    index,
+                    instruction: instruction.clone(),
+                });
+            }
+            "push" if operand == "rbp" => is_frame_pointer_pushed = true,
+            "pop" if operand == "rbp" => is_frame_pointer_pushed = false,
+            "ret" if !is_in_function || is_frame_pointer_pushed => {
+                return Err(CodegenError::RetWithoutPrologue { index });
+            }
+            _ if mnemonic.ends_with(':') && !mnemonic.starts_with('.') => {
+                is_in_function = true;
+                is_frame_pointer_pushed = false;
+            }
+            _ => {}
+        }
     }
+
+    Ok(())
 }
 
 impl X86AssemblyCodegen {
+    #[cfg(test)]
     pub fn new(program: Program) -> Self {
+        Self::with_options(program, CodegenOptions::default())
+    }
+
+    pub fn with_options(program: Program, options: CodegenOptions) -> Self {
         Self {
             program,
+            options,
             environment: Environment::default(),
         }
     }
 
+    #[cfg(test)]
     pub fn generate(&mut self) -> Vec<Instruction> {
-        let prelude = self.emit_prelude();
-        let stack_space_allocation = self.emit_stack_space_allocation();
-        let program_instructions = self
-            .program
-            .statements
-            .clone() // TODO: how to get rid of this clone?
-            .iter()
-            .flat_map(|statement| self.emit_statement(&statement))
-            .collect();
-        let epilogue = self.emit_epilogue();
+        let (instructions, _) = self.generate_with_statement_ranges().unwrap();
+
+        instructions
+    }
+
+    // also returns the range of instructions emitted for every statement of the program,
+    // followed by the range of the epilogue returning the tail, when there is one
+    pub fn generate_with_statement_ranges(
+        &mut self,
+    ) -> Result<(Vec<Instruction>, Vec<Range<usize>>), CodegenError> {
+        // variables are allocated while emitting, so every run starts from an empty environment
+        self.environment = Environment {
+            global_variables: self.collect_global_variables(),
+            omit_frame_pointer: self.options.omit_frame_pointer,
+            ..Environment::default()
+        };
+
+        let stack_space_allocation = self.emit_stack_space_allocation()?;
 
-        [
-            prelude,
-            stack_space_allocation,
-            program_instructions,
-            epilogue,
+        // the body is emitted first, because the prelude declares the functions it calls
+        let mut body = vec![];
+        let mut body_ranges = vec![];
+
+        // TODO: how to get rid of this clone?
+        for (index, statement) in self.program.statements.clone().into_iter().enumerate() {
+            if self.options.emit_statement_labels {
+                body.push(format!(".stmt_{}:", index));
+            }
+
+            let start = body.len();
+            body.extend(self.emit_statement(&statement));
+            body_ranges.push(start..body.len());
+        }
+
+        let epilogue_start = body.len();
+        body.extend(self.emit_epilogue());
+        if self.program.tail.is_some() {
+            body_ranges.push(epilogue_start..body.len());
+        }
+
+        let header = [self.emit_prelude(), stack_space_allocation].concat();
+        let statement_ranges = body_ranges
+            .into_iter()
+            .map(|range| range.start + header.len()..range.end + header.len())
+            .collect();
+        let instructions = [
+            header,
+            body,
+            self.emit_overflow_trap(),
+            self.emit_read_only_data(),
+            self.emit_data(),
         ]
-        .concat()
+        .concat();
+
+        Ok((instructions, statement_ranges))
+    }
+
+    // reads the environment of the last generate call, the instructions can be the optimized ones
+    pub fn stats(&self, instructions: &[Instruction]) -> CodegenStats {
+        CodegenStats {
+            instruction_count: instructions.len(),
+            stack_bytes: self.environment.stack_size,
+            variable_count: self.environment.allocated_variables.len()
+                + self.environment.global_variables.len(),
+            extern_count: instructions
+                .iter()
+                .filter(|instruction| instruction.starts_with("extern "))
+                .count(),
+        }
     }
 
     fn emit_prelude(&self) -> Vec<Instruction> {
-        vec![
-            "global main".to_string(),
-            "extern print_int".to_string(),
-            "section .text".to_string(),
-            "main:".to_string(),
-            "push rbp".to_string(),
-            "mov rbp, rsp".to_string(),
-        ]
+        let externs = RUNTIME_FUNCTIONS
+            .iter()
+            .map(|function| function.to_string())
+            .chain(self.environment.called_extern_functions.iter().cloned())
+            .map(|function| format!("extern {}", function));
+
+        // nasm has no .ident directive, so the producer is recorded as a comment
+        let ident = format!(
+            "; generated by {} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+
+        self.options
+            .emit_ident
+            .then_some(ident)
+            .into_iter()
+            .chain(["global main".to_string()])
+            .chain(externs)
+            .chain(["section .text".to_string(), "main:".to_string()])
+            .chain(self.options.emit_endbranch.then(|| "endbr64".to_string()))
+            .chain(self.emit_frame_setup())
+            .collect()
     }
 
-    fn emit_epilogue(&self) -> Vec<Instruction> {
-        vec![
-            "mov rsp, rbp".to_string(),
-            "pop rbp".to_string(),
-            "xor rax, rax".to_string(),
-            "ret".to_string(),
-        ]
+    fn emit_frame_setup(&self) -> Vec<Instruction> {
+        if self.options.omit_frame_pointer {
+            return vec![];
+        }
+
+        vec!["push rbp".to_string(), "mov rbp, rsp".to_string()]
     }
 
-    fn emit_stack_space_allocation(&self) -> Vec<Instruction> {
-        // this function assumes we are operating on 32-bit integers for now
-        let bytes_needed: u32 = self
+    fn emit_frame_teardown(&self) -> Vec<Instruction> {
+        if !self.options.omit_frame_pointer {
+            return vec!["mov rsp, rbp".to_string(), "pop rbp".to_string()];
+        }
+
+        match self.environment.frame_size {
+            0 => vec![],
+            frame_size => vec![format!("add rsp, {}", frame_size)],
+        }
+    }
+
+    fn emit_read_only_data(&self) -> Vec<Instruction> {
+        if self.environment.string_literals.is_empty() {
+            return vec![];
+        }
+
+        let literals = self
+            .environment
+            .string_literals
+            .iter()
+            .enumerate()
+            .map(|(index, value)| format!("str_{}: db `{}`, 0", index, Self::escape_string(value)));
+
+        ["section .rodata".to_string()]
+            .into_iter()
+            .chain(literals)
+            .collect()
+    }
+
+    // nasm decodes escape sequences in backtick strings, so the ones the tokenizer
+    // applied are written back, together with the backtick itself
+    fn escape_string(value: &str) -> String {
+        let mut escaped = String::new();
+
+        for c in value.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '`' => escaped.push_str("\\`"),
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                '\r' => escaped.push_str("\\r"),
+                _ => escaped.push(c),
+            }
+        }
+
+        escaped
+    }
+
+    fn emit_data(&self) -> Vec<Instruction> {
+        if self.environment.global_variables.is_empty() {
+            return vec![];
+        }
+
+        let globals = self
+            .environment
+            .global_variables
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}: dd {}",
+                    Environment::global_label(name),
+                    Self::format_dword_immediate(*value)
+                )
+            });
+
+        ["section .data".to_string()]
+            .into_iter()
+            .chain(globals)
+            .collect()
+    }
+
+    fn emit_epilogue(&mut self) -> Vec<Instruction> {
+        let Some(tail) = self.program.tail.clone() else {
+            return [
+                self.emit_frame_teardown(),
+                vec!["xor rax, rax".to_string(), "ret".to_string()],
+            ]
+            .concat();
+        };
+
+        // the value of the tail expression is returned from main, so it becomes the exit code
+        let mut instructions = match &tail {
+            Expression::Constant { value } => vec![format!("mov rax, {}", value)],
+            Expression::Boolean { value } => vec![format!("mov rax, {}", i64::from(*value))],
+            Expression::VariableAccess { name, .. } => {
+                vec![format!(
+                    "movsxd rax, {}",
+                    self.environment.variable_operand(name)
+                )]
+            }
+            Expression::Call { name, args } => {
+                let mut instructions = self.emit_function_call(name, args);
+                // the functions of runtime.c return nothing, eax is whatever they left in it
+                if RUNTIME_FUNCTIONS.contains(&name.as_str())
+                    || OPTIONAL_RUNTIME_FUNCTIONS.contains(&name.as_str())
+                {
+                    instructions.push("xor rax, rax".to_string());
+                } else {
+                    instructions.push("movsxd rax, eax".to_string());
+                }
+                instructions
+            }
+            Expression::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                let mut instructions = self.emit_binary_op(left, operator, right);
+                instructions.push("movsxd rax, eax".to_string());
+                instructions
+            }
+            Expression::UnaryOp { operator, operand } => {
+                let mut instructions = self.emit_unary_op(operator, operand);
+                instructions.push("movsxd rax, eax".to_string());
+                instructions
+            }
+            // the exit code is widened to rax anyway
+            Expression::Cast { expression, .. } => self.emit_cast(expression, &Type::I64),
+            _ => panic!("Tried to return a non atomic expression from the program"),
+        };
+
+        instructions.extend(self.emit_frame_teardown());
+        instructions.push("ret".to_string());
+
+        instructions
+    }
+
+    fn emit_stack_space_allocation(&mut self) -> Result<Vec<Instruction>, CodegenError> {
+        let variable_count = self
             .program
             .statements
             .iter()
-            .filter_map(|s| match s {
-                Statement::Expression(_) => None,
-                Statement::VariableDeclaration { .. } => Some(4),
+            .filter(|s| match s {
+                Statement::VariableDeclaration { name, .. } => {
+                    !self.environment.is_global_variable(name)
+                }
+                Statement::Expression(_) => false,
             })
-            .sum();
+            .count();
+        let aligned_space = Self::aligned_stack_space(variable_count)?;
+        self.environment.stack_size = aligned_space;
+
+        if self.options.use_red_zone && aligned_space <= RED_ZONE_SIZE && !self.has_calls() {
+            return Ok(vec![]);
+        }
+
+        // without the pushed rbp, rsp is 8 bytes short of the 16 byte alignment calls need
+        if self.options.omit_frame_pointer {
+            self.environment.frame_size = aligned_space + 8;
+            return Ok(vec![format!("sub rsp, {}", self.environment.frame_size)]);
+        }
+
+        if aligned_space > 0 {
+            Ok(vec![format!("sub rsp, {}", aligned_space)])
+        } else {
+            Ok(vec![])
+        }
+    }
 
-        if bytes_needed > 0 {
+    fn aligned_stack_space(variable_count: usize) -> Result<u32, CodegenError> {
+        // this function assumes we are operating on 32-bit integers for now
+        u32::try_from(variable_count)
+            .ok()
+            .and_then(|count| count.checked_mul(4))
             // add 15 to get above the next multiple of 16
             // then clear the last 4 bits to round down to multiple of 16
-            let aligned_space = (bytes_needed + 15) & !15;
-            vec![format!("sub rsp, {}", aligned_space).to_string()]
-        } else {
-            vec![]
+            .and_then(|bytes_needed| bytes_needed.checked_add(15))
+            .map(|bytes_needed| bytes_needed & !15)
+            .filter(|&aligned_space| aligned_space <= MAX_STACK_SIZE)
+            .ok_or(CodegenError::StackTooLarge)
+    }
+
+    // a variable declared more than once needs a slot for every declaration, so it stays
+    // on the stack
+    fn collect_global_variables(&self) -> Vec<(String, i64)> {
+        if !self.options.constant_globals {
+            return vec![];
+        }
+
+        let mut declaration_counts: HashMap<&String, usize> = HashMap::new();
+        for statement in &self.program.statements {
+            if let Statement::VariableDeclaration { name, .. } = statement {
+                *declaration_counts.entry(name).or_default() += 1;
+            }
         }
+
+        self.program
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::VariableDeclaration {
+                    name,
+                    value: Expression::Constant { value },
+                } if declaration_counts[name] == 1 => Some((name.clone(), *value)),
+                Statement::VariableDeclaration {
+                    name,
+                    value: Expression::Boolean { value },
+                } if declaration_counts[name] == 1 => Some((name.clone(), i64::from(*value))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn has_calls(&self) -> bool {
+        let statement_expressions = self.program.statements.iter().map(|s| match s {
+            Statement::Expression(expression) => expression,
+            Statement::VariableDeclaration { value, .. } => value,
+        });
+
+        statement_expressions
+            .chain(self.program.tail.iter())
+            .any(Expression::contains_call)
     }
 
     fn emit_statement(&mut self, statement: &Statement) -> Vec<Instruction> {
@@ -116,132 +622,477 @@ impl X86AssemblyCodegen {
         name: &String,
         initializer: &Expression,
     ) -> Vec<Instruction> {
-        self.environment.allocate_variable(name.clone());
-        let stack_offset = self.environment.get_variable_stack_offset(name);
-
-        let value = match initializer {
-            Expression::Constant { value } => value,
-            Expression::VariableAccess { .. } => todo!(),
-            _ => {
-                dbg!(initializer);
-                panic!("Tried to initialize variable using a non atomic expression")
-            },
+        // the initial value of a global is part of .data
+        if self.environment.is_global_variable(name) {
+            return vec![];
+        }
+
+        // the initializer is computed before the variable gets its slot, so a redeclaration
+        // like let x = x + 1; still reads the previous x
+        let (mut instructions, value) = match initializer {
+            Expression::Constant { value } => (vec![], Self::format_dword_immediate(*value)),
+            Expression::Boolean { value } => (vec![], i64::from(*value).to_string()),
+            // memory to memory moves do not exist, so the value goes through eax
+            Expression::VariableAccess { name, .. } => (
+                vec![format!(
+                    "mov eax, {}",
+                    self.environment.variable_operand(name)
+                )],
+                "eax".to_string(),
+            ),
+            Expression::BinaryOp {
+                left,
+                operator,
+                right,
+            } => (
+                self.emit_binary_op(left, operator, right),
+                "eax".to_string(),
+            ),
+            Expression::UnaryOp { operator, operand } => {
+                (self.emit_unary_op(operator, operand), "eax".to_string())
+            }
+            Expression::Call { name, args } => {
+                (self.emit_function_call(name, args), "eax".to_string())
+            }
+            Expression::Cast { expression, target } => {
+                (self.emit_cast(expression, target), "eax".to_string())
+            }
+            _ => panic!("Tried to initialize variable using a non atomic expression"),
         };
 
-        let instruction = format!("mov dword [rbp - {}], {}", stack_offset, value);
+        self.environment.allocate_variable(name.clone());
+        instructions.push(format!(
+            "mov {}, {}",
+            self.environment.variable_operand(name),
+            value
+        ));
+
+        instructions
+    }
 
-        vec![instruction]
+    // negative values are written as their 32-bit two's complement, so the encoding
+    // does not depend on how the assembler sign extends a negative dword immediate
+    fn format_dword_immediate(value: i64) -> String {
+        if value < 0 {
+            format!("0x{:08X}", value as u32)
+        } else {
+            format!("{}", value)
+        }
     }
 
     fn emit_expression(&mut self, expression: &Expression) -> Vec<Instruction> {
         match expression {
             Expression::Call { name, args } => self.emit_function_call(name, args),
-            _ => todo!(),
+            // the operands are atomic, so only the result is computed and then discarded
+            Expression::BinaryOp {
+                left,
+                operator,
+                right,
+            } => self.emit_binary_op(left, operator, right),
+            Expression::UnaryOp { operator, operand } => self.emit_unary_op(operator, operand),
+            Expression::Cast { expression, target } => self.emit_cast(expression, target),
+            Expression::Constant { .. }
+            | Expression::Boolean { .. }
+            | Expression::VariableAccess { .. }
+            | Expression::StringLiteral { .. } => vec![],
+            Expression::Grouping { .. } => {
+                panic!("Tried to emit expression statement using a non atomic expression")
+            }
         }
     }
 
-    fn emit_function_call(&mut self, name: &String, args: &Vec<Expression>) -> Vec<Instruction> {
-        assert_eq!(args.len(), 1, "Function calls support exactly 1 argument");
-
-        let mut instructions = vec![];
+    // variables are 32 bits wide, so the value stays in eax, an i64 is also sign extended
+    // into rax for whoever reads all of it
+    fn emit_cast(&self, operand: &Expression, target: &Type) -> Vec<Instruction> {
+        let mut instructions = vec![format!("mov eax, {}", self.emit_operand(operand))];
+        if target == &Type::I64 {
+            instructions.push("movsxd rax, eax".to_string());
+        }
 
-        let source = match args.get(0).unwrap() {
-            Expression::Constant { value } => format!("{}", value),
-            Expression::VariableAccess { name } => {
-                let stack_offset = self.environment.get_variable_stack_offset(name);
-                instructions.push(format!("mov dword rax, [rbp - {}]", stack_offset));
+        instructions
+    }
 
-                "rax".to_string()
-            }
-            _ => panic!("Tried to pass a function argument using a non atomic expression"),
-        };
+    // leaves the result of the operation in eax, like emit_binary_op
+    fn emit_unary_op(&self, operator: &Operator, operand: &Expression) -> Vec<Instruction> {
+        let mut instructions = vec![format!("mov eax, {}", self.emit_operand(operand))];
 
-        instructions.push(format!("mov dword rdi, {}", source));
-        instructions.push(format!("call {}", name));
+        match operator {
+            Operator::Sub => instructions.extend(match self.options.arithmetic_mode {
+                ArithmeticMode::Wrap => vec!["neg eax".to_string()],
+                ArithmeticMode::Check => {
+                    vec!["neg eax".to_string(), format!("jo {}", OVERFLOW_TRAP_LABEL)]
+                }
+                // only negating i32::MIN overflows, its exact result is above i32::MAX
+                ArithmeticMode::Saturate => vec![
+                    "mov ecx, 0x7FFFFFFF".to_string(),
+                    "neg eax".to_string(),
+                    "cmovo eax, ecx".to_string(),
+                ],
+            }),
+            Operator::Add => {}
+            _ => panic!(
+                "Cannot apply operator {:?} to an operand in UnaryOp",
+                operator
+            ),
+        }
 
         instructions
     }
-}
 
-#[cfg(test)]
-mod test {
-    use pretty_assertions::assert_eq;
+    // leaves the result of the operation in eax
+    fn emit_binary_op(
+        &self,
+        left: &Expression,
+        operator: &Operator,
+        right: &Expression,
+    ) -> Vec<Instruction> {
+        let mut instructions = vec![format!("mov eax, {}", self.emit_operand(left))];
+        let right_operand = self.emit_operand(right);
 
-    use super::*;
+        match operator {
+            Operator::Add => instructions.extend(self.emit_overflowing_op("add", &right_operand)),
+            Operator::Sub => instructions.extend(self.emit_overflowing_op("sub", &right_operand)),
+            Operator::Multiply => {
+                instructions.extend(self.emit_overflowing_op("imul", &right_operand))
+            }
+            Operator::Modulo if Self::power_of_two_exponent(right).is_some() => {
+                instructions.extend(Self::emit_masked_modulo(right))
+            }
+            Operator::Divide | Operator::Modulo => {
+                // idiv divides edx:eax and does not accept an immediate divisor
+                let divisor = if matches!(
+                    right,
+                    Expression::Constant { .. } | Expression::Boolean { .. }
+                ) {
+                    instructions.push(format!("mov ecx, {}", right_operand));
+                    "ecx".to_string()
+                } else {
+                    right_operand
+                };
+                instructions.push("cdq".to_string());
+                instructions.push(format!("idiv {}", divisor));
 
-    #[test]
-    fn declare_variable_with_constant_initializer() {
-        // given
-        let program = Program {
-            statements: vec![
-                Statement::VariableDeclaration {
-                    name: "foo".to_string(),
-                    value: Expression::Constant { value: 4 },
-                },
-                Statement::VariableDeclaration {
-                    name: "bar".to_string(),
-                    value: Expression::Constant { value: 42 },
-                },
-                Statement::VariableDeclaration {
-                    name: "baz".to_string(),
-                    value: Expression::Constant { value: 127 },
-                },
-            ],
-        };
+                // the quotient is left in eax and the remainder in edx
+                if operator == &Operator::Modulo {
+                    instructions.push("mov eax, edx".to_string());
+                }
+            }
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessEqual
+            | Operator::Greater
+            | Operator::GreaterEqual => {
+                let condition = match operator {
+                    Operator::Equal => "e",
+                    Operator::NotEqual => "ne",
+                    Operator::Less => "l",
+                    Operator::LessEqual => "le",
+                    Operator::Greater => "g",
+                    _ => "ge",
+                };
+                instructions.push(format!("cmp eax, {}", right_operand));
+                instructions.push(format!("set{} al", condition));
+                instructions.push("movzx eax, al".to_string());
+            }
+        }
 
-        let mut codegen = X86AssemblyCodegen::new(program);
+        instructions
+    }
 
-        // when
-        let result = codegen.generate();
+    // the exponent of a constant divisor whose remainder can be masked instead of computed
+    // with idiv, 2^31 is left out because it does not fit in 32 bits
+    fn power_of_two_exponent(divisor: &Expression) -> Option<u32> {
+        match divisor {
+            Expression::Constant { value }
+                if (2..=1 << 30).contains(value) && value.count_ones() == 1 =>
+            {
+                Some(value.trailing_zeros())
+            }
+            _ => None,
+        }
+    }
 
-        // then
+    // the mask alone is wrong for a negative dividend, -9 & 7 is 7 while -9 % 8 is -1. a
+    // negative dividend is biased by 2^k - 1 before the mask and the bias is subtracted after,
+    // so the remainder keeps the sign of the dividend like idiv leaves it
+    fn emit_masked_modulo(divisor: &Expression) -> Vec<Instruction> {
+        let exponent = Self::power_of_two_exponent(divisor)
+            .unwrap_or_else(|| panic!("Cannot mask the remainder of a division by {}", divisor));
 
-        assert_eq!(
-            vec![
-                "global main",
-                "extern print_int",
-                "section .text",
-                "main:",
-                "push rbp",
-                "mov rbp, rsp",
-                "sub rsp, 16",
-                "mov dword [rbp - 4], 4",
-                "mov dword [rbp - 8], 42",
-                "mov dword [rbp - 12], 127",
-                "mov rsp, rbp",
-                "pop rbp",
-                "xor rax, rax",
-                "ret"
-            ],
-            result
-        )
+        vec![
+            "mov ecx, eax".to_string(),
+            "sar ecx, 31".to_string(),
+            format!("shr ecx, {}", 32 - exponent),
+            "add eax, ecx".to_string(),
+            format!("and eax, {}", (1 << exponent) - 1),
+            "sub eax, ecx".to_string(),
+        ]
     }
 
-    #[test]
-    fn emit_function_call_with_constant_argument() {
-        // given
-        let program = Program {
-            statements: vec![Statement::Expression(Expression::Call {
-                name: "print_int".to_string(),
-                args: vec![Expression::Constant { value: 4 }],
-            })],
-        };
+    // add, sub and imul set the overflow flag when the result does not fit in 32 bits,
+    // the arithmetic mode decides what happens to eax then
+    fn emit_overflowing_op(&self, mnemonic: &str, operand: &str) -> Vec<Instruction> {
+        let instruction = format!("{} eax, {}", mnemonic, operand);
 
-        let mut codegen = X86AssemblyCodegen::new(program);
+        match self.options.arithmetic_mode {
+            ArithmeticMode::Wrap => vec![instruction],
+            ArithmeticMode::Check => vec![instruction, format!("jo {}", OVERFLOW_TRAP_LABEL)],
+            ArithmeticMode::Saturate => {
+                // ecx gets the bound with the sign of the exact result, which is the sign of
+                // the left operand for add and sub, and the sign of the product for imul
+                let mut instructions = vec!["mov ecx, eax".to_string()];
+                if mnemonic == "imul" {
+                    instructions.push(format!("xor ecx, {}", operand));
+                }
+                instructions.extend([
+                    "sar ecx, 31".to_string(),
+                    "xor ecx, 0x7FFFFFFF".to_string(),
+                    instruction,
+                    "cmovo eax, ecx".to_string(),
+                ]);
 
-        // when
-        let result = codegen.generate();
+                instructions
+            }
+        }
+    }
 
-        // then
-        assert_eq!(
+    // ud2 stops the program with SIGILL, the same trap sanitizers use for overflow
+    fn emit_overflow_trap(&self) -> Vec<Instruction> {
+        if self.options.arithmetic_mode != ArithmeticMode::Check {
+            return vec![];
+        }
+
+        vec![format!("{}:", OVERFLOW_TRAP_LABEL), "ud2".to_string()]
+    }
+
+    fn emit_operand(&self, operand: &Expression) -> String {
+        match operand {
+            Expression::Constant { value } => format!("{}", value),
+            Expression::Boolean { value } => format!("{}", i64::from(*value)),
+            Expression::VariableAccess { name, .. } => self.environment.variable_operand(name),
+            _ => panic!("Tried to use a non atomic expression as an operand"),
+        }
+    }
+
+    fn emit_function_call(&mut self, name: &String, args: &[Expression]) -> Vec<Instruction> {
+        if MIN_MAX_INTRINSICS.contains(&name.as_str()) {
+            return self.emit_min_max(name, args);
+        }
+
+        let mut instructions = vec![];
+
+        let register_args_count = args.len().min(ARGUMENT_REGISTERS.len());
+        let (register_args, stack_args) = args.split_at(register_args_count);
+
+        // the stack has to be 16 byte aligned at the call, and every pushed argument takes 8 bytes
+        let stack_padding = if stack_args.len() % 2 == 1 { 8 } else { 0 };
+        if stack_padding > 0 {
+            instructions.push(format!("sub rsp, {}", stack_padding));
+            self.environment.pushed_bytes += stack_padding;
+        }
+
+        // arguments beyond the registers are passed on the stack, the last one is pushed first
+        for arg in stack_args.iter().rev() {
+            let source = self.emit_argument_source(arg, &mut instructions);
+            instructions.push(format!("push {}", source));
+            self.environment.pushed_bytes += 8;
+        }
+
+        // the callee reads the argument as an int, but the whole register is filled with the
+        // sign extended value, so that a negative number is still negative as a 64 bit value
+        // a variable passed more than once is loaded from the stack only the first time,
+        // nothing can change it before the call, which ends the reuse
+        let mut loaded_variables: HashMap<&String, &str> = HashMap::new();
+        for (arg, register) in register_args.iter().zip(ARGUMENT_REGISTERS) {
+            let instruction = match arg {
+                Expression::VariableAccess { name, .. } => match loaded_variables.get(name) {
+                    Some(loaded_register) => format!("mov {}, {}", register, loaded_register),
+                    None => {
+                        loaded_variables.insert(name, register);
+                        format!("movsxd {}, {}", register, self.emit_operand(arg))
+                    }
+                },
+                Expression::StringLiteral { value } => {
+                    let label = self.environment.add_string_literal(value);
+                    format!("lea {}, [rel {}]", register, label)
+                }
+                _ => format!("mov {}, {}", register, self.emit_operand(arg)),
+            };
+            instructions.push(instruction);
+        }
+
+        if OPTIONAL_RUNTIME_FUNCTIONS.contains(&name.as_str()) {
+            self.environment
+                .called_extern_functions
+                .insert(name.clone());
+        }
+
+        if VARIADIC_LIBC_FUNCTIONS.contains(&name.as_str()) {
+            self.environment
+                .called_extern_functions
+                .insert(name.clone());
+            // al holds the number of vector registers used for arguments, there are none
+            instructions.push("xor eax, eax".to_string());
+        }
+
+        instructions.push(format!("call {}", name));
+
+        let stack_args_size = self.environment.pushed_bytes;
+        if stack_args_size > 0 {
+            instructions.push(format!("add rsp, {}", stack_args_size));
+            self.environment.pushed_bytes = 0;
+        }
+
+        instructions
+    }
+
+    // leaves the result in eax, like a call would
+    fn emit_min_max(&self, name: &str, args: &[Expression]) -> Vec<Instruction> {
+        let Some((first, rest)) = args.split_first() else {
+            panic!("{} needs at least one argument", name);
+        };
+        // the candidate replaces the current result when the result is on the wrong side of it
+        let condition = if name == "min" { "g" } else { "l" };
+
+        let mut instructions = vec![format!("mov eax, {}", self.emit_operand(first))];
+        for arg in rest {
+            instructions.extend([
+                format!("mov ecx, {}", self.emit_operand(arg)),
+                "cmp eax, ecx".to_string(),
+                format!("cmov{} eax, ecx", condition),
+            ]);
+        }
+
+        instructions
+    }
+
+    fn emit_argument_source(
+        &mut self,
+        arg: &Expression,
+        instructions: &mut Vec<Instruction>,
+    ) -> String {
+        match arg {
+            Expression::Constant { value } => format!("{}", value),
+            Expression::Boolean { value } => format!("{}", i64::from(*value)),
+            Expression::VariableAccess { name, .. } => {
+                let variable = self.environment.variable_operand(name);
+                instructions.push(format!("movsxd rax, {}", variable));
+
+                "rax".to_string()
+            }
+            Expression::StringLiteral { value } => {
+                let label = self.environment.add_string_literal(value);
+                instructions.push(format!("lea rax, [rel {}]", label));
+
+                "rax".to_string()
+            }
+            _ => panic!("Tried to pass a function argument using a non atomic expression"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    // the first line of every generated program unless emit_ident is turned off
+    const IDENT: &str = concat!("; generated by yep ", env!("CARGO_PKG_VERSION"));
+
+    fn variable(name: &str) -> Expression {
+        Expression::VariableAccess {
+            name: name.to_string(),
+            location: (0, 0),
+        }
+    }
+
+    fn declare(name: &str, value: i64) -> Statement {
+        Statement::VariableDeclaration {
+            name: name.to_string(),
+            value: Expression::Constant { value },
+        }
+    }
+
+    #[test]
+    fn declare_variable_with_constant_initializer() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "foo".to_string(),
+                    value: Expression::Constant { value: 4 },
+                },
+                Statement::VariableDeclaration {
+                    name: "bar".to_string(),
+                    value: Expression::Constant { value: 42 },
+                },
+                Statement::VariableDeclaration {
+                    name: "baz".to_string(),
+                    value: Expression::Constant { value: 127 },
+                },
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "section .text",
+                "main:",
+                "push rbp",
+                "mov rbp, rsp",
+                "mov dword [rbp - 4], 4",
+                "mov dword [rbp - 8], 42",
+                "mov dword [rbp - 12], 127",
+                "mov rsp, rbp",
+                "pop rbp",
+                "xor rax, rax",
+                "ret"
+            ],
+            result
+        )
+    }
+
+    #[test]
+    fn emit_function_call_with_constant_argument() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::Constant { value: 4 }],
+            })],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
             vec![
+                IDENT,
                 "global main",
                 "extern print_int",
+                "extern println_int",
                 "section .text",
                 "main:",
                 "push rbp",
                 "mov rbp, rsp",
-                "mov dword rdi, 4",
+                "mov rdi, 4",
                 "call print_int",
                 "mov rsp, rbp",
                 "pop rbp",
@@ -251,4 +1102,1369 @@ mod test {
             result
         )
     }
+
+    #[test]
+    fn emit_function_call_with_arguments_passed_on_stack() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "sum7".to_string(),
+                args: (1..=7)
+                    .map(|value| Expression::Constant { value })
+                    .collect(),
+            })],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "section .text",
+                "main:",
+                "push rbp",
+                "mov rbp, rsp",
+                "sub rsp, 8",
+                "push 7",
+                "mov rdi, 1",
+                "mov rsi, 2",
+                "mov rdx, 3",
+                "mov rcx, 4",
+                "mov r8, 5",
+                "mov r9, 6",
+                "call sum7",
+                "add rsp, 16",
+                "mov rsp, rbp",
+                "pop rbp",
+                "xor rax, rax",
+                "ret"
+            ],
+            result
+        )
+    }
+
+    #[test]
+    fn return_tail_expression_from_main() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "foo".to_string(),
+                value: Expression::Constant { value: 42 },
+            }],
+            tail: Some(Expression::VariableAccess {
+                name: "foo".to_string(),
+                location: (0, 0),
+            }),
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "section .text",
+                "main:",
+                "push rbp",
+                "mov rbp, rsp",
+                "mov dword [rbp - 4], 42",
+                "movsxd rax, dword [rbp - 4]",
+                "mov rsp, rbp",
+                "pop rbp",
+                "ret"
+            ],
+            result
+        )
+    }
+
+    #[test]
+    fn exit_with_zero_after_runtime_call_in_tail() {
+        // given
+        let program = Program {
+            statements: vec![],
+            tail: Some(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::Constant { value: 3 }],
+            }),
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "section .text",
+                "main:",
+                "push rbp",
+                "mov rbp, rsp",
+                "mov rdi, 3",
+                "call print_int",
+                "xor rax, rax",
+                "mov rsp, rbp",
+                "pop rbp",
+                "ret"
+            ],
+            result
+        )
+    }
+
+    #[test]
+    fn allocate_stack_space_when_program_makes_calls() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "foo".to_string(),
+                    value: Expression::Constant { value: 4 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![Expression::VariableAccess {
+                        name: "foo".to_string(),
+                        location: (0, 0),
+                    }],
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "section .text",
+                "main:",
+                "push rbp",
+                "mov rbp, rsp",
+                "sub rsp, 16",
+                "mov dword [rbp - 4], 4",
+                "movsxd rdi, dword [rbp - 4]",
+                "call print_int",
+                "mov rsp, rbp",
+                "pop rbp",
+                "xor rax, rax",
+                "ret"
+            ],
+            result
+        )
+    }
+
+    #[test]
+    fn allocate_stack_space_when_red_zone_is_disabled() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "foo".to_string(),
+                value: Expression::Constant { value: 4 },
+            }],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::with_options(
+            program,
+            CodegenOptions {
+                use_red_zone: false,
+                ..CodegenOptions::default()
+            },
+        );
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert!(result.contains(&"sub rsp, 16".to_string()));
+    }
+
+    #[test]
+    fn evaluate_discarded_binary_op_for_side_effects() {
+        // given
+        // f() + g();
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "tmp_0".to_string(),
+                    value: Expression::Call {
+                        name: "f".to_string(),
+                        args: vec![],
+                    },
+                },
+                Statement::VariableDeclaration {
+                    name: "tmp_1".to_string(),
+                    value: Expression::Call {
+                        name: "g".to_string(),
+                        args: vec![],
+                    },
+                },
+                Statement::Expression(Expression::BinaryOp {
+                    left: Box::new(Expression::VariableAccess {
+                        name: "tmp_0".to_string(),
+                        location: (0, 0),
+                    }),
+                    operator: Operator::Add,
+                    right: Box::new(Expression::VariableAccess {
+                        name: "tmp_1".to_string(),
+                        location: (0, 0),
+                    }),
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "section .text",
+                "main:",
+                "push rbp",
+                "mov rbp, rsp",
+                "sub rsp, 16",
+                "call f",
+                "mov dword [rbp - 4], eax",
+                "call g",
+                "mov dword [rbp - 8], eax",
+                "mov eax, dword [rbp - 4]",
+                "add eax, dword [rbp - 8]",
+                "mov rsp, rbp",
+                "pop rbp",
+                "xor rax, rax",
+                "ret"
+            ],
+            result
+        )
+    }
+
+    #[test]
+    fn emit_negative_constant_as_twos_complement() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "x".to_string(),
+                value: Expression::Constant { value: -1 },
+            }],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert!(result.contains(&"mov dword [rbp - 4], 0xFFFFFFFF".to_string()));
+    }
+
+    #[test]
+    fn sign_extend_negative_arguments_into_full_register() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: -5 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![
+                        Expression::Constant { value: -5 },
+                        Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        },
+                    ],
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert!(result.contains(&"mov rdi, -5".to_string()));
+        assert!(result.contains(&"movsxd rsi, dword [rbp - 4]".to_string()));
+    }
+
+    #[test]
+    fn store_binary_op_initializer_in_variable() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "a".to_string(),
+                    value: Expression::Constant { value: 1 },
+                },
+                Statement::VariableDeclaration {
+                    name: "b".to_string(),
+                    value: Expression::Constant { value: 2 },
+                },
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(variable("a")),
+                        operator: Operator::Add,
+                        right: Box::new(variable("b")),
+                    },
+                },
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov dword [rbp - 4], 1",
+                "mov dword [rbp - 8], 2",
+                "mov eax, dword [rbp - 4]",
+                "add eax, dword [rbp - 8]",
+                "mov dword [rbp - 12], eax",
+            ],
+            result[8..13]
+        )
+    }
+
+    #[test]
+    fn copy_variable_initializer_through_eax() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "foo".to_string(),
+                    value: Expression::Constant { value: 5 },
+                },
+                Statement::VariableDeclaration {
+                    name: "bar".to_string(),
+                    value: Expression::VariableAccess {
+                        name: "foo".to_string(),
+                        location: (0, 0),
+                    },
+                },
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov dword [rbp - 4], 5",
+                "mov eax, dword [rbp - 4]",
+                "mov dword [rbp - 8], eax",
+            ],
+            result[8..11]
+        )
+    }
+
+    #[test]
+    fn read_previous_variable_when_redeclaring_it() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 1 },
+                },
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::VariableAccess {
+                        name: "x".to_string(),
+                        location: (0, 0),
+                    },
+                },
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec!["mov eax, dword [rbp - 4]", "mov dword [rbp - 8], eax"],
+            result[9..11]
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable foo is used before it is declared")]
+    fn report_undeclared_variable_initializer() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "bar".to_string(),
+                value: Expression::VariableAccess {
+                    name: "foo".to_string(),
+                    location: (0, 0),
+                },
+            }],
+            tail: None,
+        };
+
+        // when & then
+        X86AssemblyCodegen::new(program).generate();
+    }
+
+    #[test]
+    fn negate_variable_into_another() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 7 },
+                },
+                Statement::VariableDeclaration {
+                    name: "y".to_string(),
+                    value: Expression::UnaryOp {
+                        operator: Operator::Sub,
+                        operand: Box::new(Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        }),
+                    },
+                },
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov dword [rbp - 4], 7",
+                "mov eax, dword [rbp - 4]",
+                "neg eax",
+                "mov dword [rbp - 8], eax",
+            ],
+            result[8..12]
+        )
+    }
+
+    #[test]
+    fn read_remainder_from_edx_for_modulo() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 7 },
+                },
+                Statement::Expression(Expression::BinaryOp {
+                    left: Box::new(Expression::VariableAccess {
+                        name: "x".to_string(),
+                        location: (0, 0),
+                    }),
+                    operator: Operator::Modulo,
+                    right: Box::new(Expression::Constant { value: 3 }),
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov dword [rbp - 4], 7",
+                "mov eax, dword [rbp - 4]",
+                "mov ecx, 3",
+                "cdq",
+                "idiv ecx",
+                "mov eax, edx",
+            ],
+            result[8..14]
+        )
+    }
+
+    #[test]
+    fn mask_remainder_by_power_of_two_keeping_the_sign() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: -9 },
+                },
+                Statement::Expression(Expression::BinaryOp {
+                    left: Box::new(Expression::VariableAccess {
+                        name: "x".to_string(),
+                        location: (0, 0),
+                    }),
+                    operator: Operator::Modulo,
+                    right: Box::new(Expression::Constant { value: 8 }),
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov dword [rbp - 4], 0xFFFFFFF7",
+                "mov eax, dword [rbp - 4]",
+                "mov ecx, eax",
+                "sar ecx, 31",
+                "shr ecx, 29",
+                "add eax, ecx",
+                "and eax, 7",
+                "sub eax, ecx",
+            ],
+            result[8..16]
+        )
+    }
+
+    #[test]
+    fn generate_same_instructions_when_called_twice() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 1 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![Expression::VariableAccess {
+                        name: "x".to_string(),
+                        location: (0, 0),
+                    }],
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let first = codegen.generate();
+        let second = codegen.generate();
+
+        // then
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn emit_label_before_every_statement() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 1 },
+                },
+                Statement::VariableDeclaration {
+                    name: "y".to_string(),
+                    value: Expression::Constant { value: 2 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![Expression::VariableAccess {
+                        name: "y".to_string(),
+                        location: (0, 0),
+                    }],
+                }),
+            ],
+            tail: None,
+        };
+        let options = CodegenOptions {
+            emit_statement_labels: true,
+            ..CodegenOptions::default()
+        };
+
+        let mut codegen = X86AssemblyCodegen::with_options(program, options);
+
+        // when
+        let (instructions, statement_ranges) = codegen.generate_with_statement_ranges().unwrap();
+
+        // then
+        for (index, range) in statement_ranges.iter().enumerate() {
+            assert_eq!(format!(".stmt_{}:", index), instructions[range.start - 1]);
+        }
+        assert_eq!(
+            vec![
+                ".stmt_0:",
+                "mov dword [rbp - 4], 1",
+                ".stmt_1:",
+                "mov dword [rbp - 8], 2",
+                ".stmt_2:",
+                "movsxd rdi, dword [rbp - 8]",
+                "call print_int",
+            ],
+            instructions[statement_ranges[0].start - 1..statement_ranges[2].end]
+        );
+    }
+
+    #[test]
+    fn emit_constant_declared_once_as_initialized_data() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "answer".to_string(),
+                    value: Expression::Constant { value: -42 },
+                },
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 1 },
+                },
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 2 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![Expression::VariableAccess {
+                        name: "answer".to_string(),
+                        location: (0, 0),
+                    }],
+                }),
+            ],
+            tail: None,
+        };
+        let options = CodegenOptions {
+            constant_globals: true,
+            ..CodegenOptions::default()
+        };
+
+        let mut codegen = X86AssemblyCodegen::with_options(program, options);
+
+        // when
+        let (instructions, statement_ranges) = codegen.generate_with_statement_ranges().unwrap();
+
+        // then
+        assert!(statement_ranges[0].is_empty());
+        assert_eq!(
+            vec![
+                "mov dword [rbp - 4], 1",
+                "mov dword [rbp - 8], 2",
+                "movsxd rdi, dword [rel global_answer]",
+                "call print_int",
+            ],
+            instructions[statement_ranges[1].start..statement_ranges[3].end]
+        );
+        assert_eq!(
+            vec!["section .data", "global_answer: dd 0xFFFFFFD6"],
+            instructions[instructions.len() - 2..]
+        );
+    }
+
+    #[test]
+    fn emit_cmov_chain_for_max_of_runtime_arguments() {
+        // given
+        let program = Program {
+            statements: vec![
+                declare("a", 1),
+                declare("b", 2),
+                declare("c", 3),
+                Statement::VariableDeclaration {
+                    name: "m".to_string(),
+                    value: Expression::Call {
+                        name: "max".to_string(),
+                        args: vec![variable("a"), variable("b"), variable("c")],
+                    },
+                },
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let (instructions, statement_ranges) = codegen.generate_with_statement_ranges().unwrap();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov eax, dword [rbp - 4]",
+                "mov ecx, dword [rbp - 8]",
+                "cmp eax, ecx",
+                "cmovl eax, ecx",
+                "mov ecx, dword [rbp - 12]",
+                "cmp eax, ecx",
+                "cmovl eax, ecx",
+                "mov dword [rbp - 16], eax",
+            ],
+            instructions[statement_ranges[3].clone()]
+        );
+        assert!(!instructions.contains(&"call max".to_string()));
+    }
+
+    #[test]
+    fn address_variables_from_rsp_without_frame_pointer() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "a".to_string(),
+                    value: Expression::Constant { value: 1 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "f".to_string(),
+                    args: vec![
+                        variable("a"),
+                        Expression::Constant { value: 2 },
+                        Expression::Constant { value: 3 },
+                        Expression::Constant { value: 4 },
+                        Expression::Constant { value: 5 },
+                        Expression::Constant { value: 6 },
+                        variable("a"),
+                    ],
+                }),
+            ],
+            tail: Some(variable("a")),
+        };
+        let options = CodegenOptions {
+            omit_frame_pointer: true,
+            ..CodegenOptions::default()
+        };
+
+        let mut codegen = X86AssemblyCodegen::with_options(program, options);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert!(result
+            .iter()
+            .all(|instruction| !instruction.contains("rbp")));
+        assert_eq!(
+            vec![
+                "main:",
+                "sub rsp, 24",
+                "mov dword [rsp + 20], 1",
+                "sub rsp, 8",
+                "movsxd rax, dword [rsp + 28]",
+                "push rax",
+                "movsxd rdi, dword [rsp + 36]",
+                "mov rsi, 2",
+                "mov rdx, 3",
+                "mov rcx, 4",
+                "mov r8, 5",
+                "mov r9, 6",
+                "call f",
+                "add rsp, 16",
+                "movsxd rax, dword [rsp + 20]",
+                "add rsp, 24",
+                "ret",
+            ],
+            result[result.len() - 17..]
+        );
+    }
+
+    #[test]
+    fn name_the_compiler_unless_disabled() {
+        // given
+        let options = CodegenOptions {
+            emit_ident: false,
+            ..CodegenOptions::default()
+        };
+
+        // when
+        let result = X86AssemblyCodegen::new(Program::default()).generate();
+        let result_without_ident =
+            X86AssemblyCodegen::with_options(Program::default(), options).generate();
+
+        // then
+        assert_eq!(IDENT, result[0]);
+        assert_eq!(result[1..], result_without_ident);
+    }
+
+    #[test]
+    fn report_stats_of_generated_code() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 3 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![Expression::VariableAccess {
+                        name: "x".to_string(),
+                        location: (0, 0),
+                    }],
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let instructions = codegen.generate();
+        let result = codegen.stats(&instructions).to_string();
+
+        // then
+        assert_eq!(
+            format!(
+                "instructions: {}, stack bytes: 16, variables: 1, externs: 2",
+                instructions.len()
+            ),
+            result
+        );
+        assert_eq!(16, instructions.len());
+    }
+
+    #[test]
+    fn start_main_with_endbranch_when_enabled() {
+        // given
+        let options = CodegenOptions {
+            emit_endbranch: true,
+            ..CodegenOptions::default()
+        };
+
+        // when
+        let result = X86AssemblyCodegen::with_options(Program::default(), options).generate();
+        let result_without_endbranch = X86AssemblyCodegen::new(Program::default()).generate();
+
+        // then
+        let entry = result.iter().position(|i| i == "main:").unwrap();
+        assert_eq!(
+            vec!["main:", "endbr64", "push rbp"],
+            result[entry..entry + 3]
+        );
+        assert!(!result_without_endbranch.contains(&"endbr64".to_string()));
+    }
+
+    #[test]
+    fn report_stack_too_large_instead_of_wrapping() {
+        // given
+        // 4 bytes for each of these variables is exactly 2^32, which would wrap to 0
+        let variable_count = 1 << 30;
+
+        // when
+        let result = X86AssemblyCodegen::aligned_stack_space(variable_count);
+
+        // then
+        assert_eq!(Err(CodegenError::StackTooLarge), result);
+        assert_eq!(Ok(16), X86AssemblyCodegen::aligned_stack_space(3));
+    }
+
+    #[test]
+    fn store_comparison_result_as_zero_or_one() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 5 },
+                },
+                Statement::VariableDeclaration {
+                    name: "is_small".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        }),
+                        operator: Operator::Less,
+                        right: Box::new(Expression::Constant { value: 10 }),
+                    },
+                },
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov dword [rbp - 4], 5",
+                "mov eax, dword [rbp - 4]",
+                "cmp eax, 10",
+                "setl al",
+                "movzx eax, al",
+                "mov dword [rbp - 8], eax",
+            ],
+            result[8..14]
+        )
+    }
+
+    #[test]
+    fn declare_and_call_println_int() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "println_int".to_string(),
+                args: vec![Expression::Constant { value: 5 }],
+            })],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert!(result.contains(&"extern println_int".to_string()));
+        assert!(result.contains(&"call println_int".to_string()));
+    }
+
+    #[test]
+    fn declare_and_call_radix_print_functions() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::Expression(Expression::Call {
+                    name: "print_int_hex".to_string(),
+                    args: vec![Expression::Constant { value: 255 }],
+                }),
+                Statement::Expression(Expression::Call {
+                    name: "print_int_bin".to_string(),
+                    args: vec![Expression::Constant { value: 255 }],
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "extern print_int_bin",
+                "extern print_int_hex",
+            ],
+            result[..6]
+        );
+        assert!(result.contains(&"call print_int_hex".to_string()));
+        assert!(result.contains(&"call print_int_bin".to_string()));
+        assert!(!X86AssemblyCodegen::new(Program::default())
+            .generate()
+            .contains(&"extern print_int_hex".to_string()));
+    }
+
+    #[test]
+    fn load_variable_passed_twice_only_once() {
+        // given
+        let variable = Expression::VariableAccess {
+            name: "x".to_string(),
+            location: (0, 0),
+        };
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 3 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "add".to_string(),
+                    args: vec![variable.clone(), variable],
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        let loads: Vec<&Instruction> = result
+            .iter()
+            .filter(|instruction| instruction.starts_with("movsxd"))
+            .collect();
+        assert_eq!(vec!["movsxd rdi, dword [rbp - 4]"], loads);
+        assert!(result.contains(&"mov rsi, rdi".to_string()));
+    }
+
+    #[test]
+    fn return_binary_op_tail_from_main() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "x".to_string(),
+                value: Expression::Constant { value: 40 },
+            }],
+            tail: Some(Expression::BinaryOp {
+                left: Box::new(Expression::VariableAccess {
+                    name: "x".to_string(),
+                    location: (0, 0),
+                }),
+                operator: Operator::Add,
+                right: Box::new(Expression::Constant { value: 2 }),
+            }),
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov eax, dword [rbp - 4]",
+                "add eax, 2",
+                "movsxd rax, eax",
+                "mov rsp, rbp",
+                "pop rbp",
+                "ret"
+            ],
+            result[result.len() - 6..]
+        )
+    }
+
+    #[test]
+    fn emit_printf_with_format_string_in_rodata() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 5 },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "printf".to_string(),
+                    args: vec![
+                        Expression::StringLiteral {
+                            value: "%d\n".to_string(),
+                        },
+                        Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        },
+                    ],
+                }),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let result = codegen.generate();
+
+        // then
+        assert_eq!(
+            vec![
+                IDENT,
+                "global main",
+                "extern print_int",
+                "extern println_int",
+                "extern printf",
+                "section .text",
+                "main:",
+                "push rbp",
+                "mov rbp, rsp",
+                "sub rsp, 16",
+                "mov dword [rbp - 4], 5",
+                "lea rdi, [rel str_0]",
+                "movsxd rsi, dword [rbp - 4]",
+                "xor eax, eax",
+                "call printf",
+                "mov rsp, rbp",
+                "pop rbp",
+                "xor rax, rax",
+                "ret",
+                "section .rodata",
+                "str_0: db `%d\\n`, 0"
+            ],
+            result
+        )
+    }
+
+    #[test]
+    fn collect_labels_in_order_of_appearance() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::Expression(Expression::Call {
+                    name: "printf".to_string(),
+                    args: vec![Expression::StringLiteral {
+                        value: "first".to_string(),
+                    }],
+                }),
+                Statement::Expression(Expression::Call {
+                    name: "printf".to_string(),
+                    args: vec![Expression::StringLiteral {
+                        value: "second: done".to_string(),
+                    }],
+                }),
+            ],
+            tail: None,
+        };
+        let instructions = X86AssemblyCodegen::new(program).generate();
+
+        // when
+        let result = collect_labels(&instructions);
+
+        // then
+        assert_eq!(vec!["main", "str_0", "str_1"], result);
+    }
+
+    #[test]
+    fn verify_generated_instructions() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::Constant { value: 1 }],
+            })],
+            tail: None,
+        };
+        let instructions = X86AssemblyCodegen::new(program).generate();
+
+        // when
+        let result = verify_instructions(&instructions);
+
+        // then
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn report_jump_to_missing_label() {
+        // given
+        let instructions: Vec<Instruction> = [
+            "global main",
+            "section .text",
+            "main:",
+            "push rbp",
+            "mov rbp, rsp",
+            "jmp .done",
+            "call print_int",
+            "mov rsp, rbp",
+            "pop rbp",
+            "ret",
+        ]
+        .iter()
+        .map(|instruction| instruction.to_string())
+        .collect();
+
+        // when
+        let result = verify_instructions(&instructions);
+
+        // then
+        assert_eq!(
+            Err(CodegenError::UnknownJumpTarget {
+                index: 5,
+                instruction: "jmp .done".to_string(),
+            }),
+            result
+        );
+    }
+
+    fn declare_and_compute_program(operator: Operator) -> Program {
+        Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 1 },
+                },
+                Statement::VariableDeclaration {
+                    name: "y".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        }),
+                        operator,
+                        right: Box::new(Expression::Constant { value: 3 }),
+                    },
+                },
+                Statement::VariableDeclaration {
+                    name: "z".to_string(),
+                    value: Expression::UnaryOp {
+                        operator: Operator::Sub,
+                        operand: Box::new(Expression::VariableAccess {
+                            name: "y".to_string(),
+                            location: (0, 0),
+                        }),
+                    },
+                },
+            ],
+            tail: None,
+        }
+    }
+
+    #[test]
+    fn jump_to_trap_on_overflow_in_check_mode() {
+        // given
+        let options = CodegenOptions {
+            arithmetic_mode: ArithmeticMode::Check,
+            ..CodegenOptions::default()
+        };
+
+        let mut codegen =
+            X86AssemblyCodegen::with_options(declare_and_compute_program(Operator::Add), options);
+
+        // when
+        let (instructions, statement_ranges) = codegen.generate_with_statement_ranges().unwrap();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov eax, dword [rbp - 4]",
+                "add eax, 3",
+                "jo arithmetic_overflow",
+                "mov dword [rbp - 8], eax",
+                "mov eax, dword [rbp - 8]",
+                "neg eax",
+                "jo arithmetic_overflow",
+                "mov dword [rbp - 12], eax",
+            ],
+            instructions[statement_ranges[1].start..statement_ranges[2].end]
+        );
+        assert_eq!(
+            vec!["arithmetic_overflow:", "ud2"],
+            instructions[instructions.len() - 2..]
+        );
+        assert_eq!(Ok(()), verify_instructions(&instructions));
+    }
+
+    #[test]
+    fn clamp_result_on_overflow_in_saturate_mode() {
+        // given
+        let options = CodegenOptions {
+            arithmetic_mode: ArithmeticMode::Saturate,
+            ..CodegenOptions::default()
+        };
+
+        let mut codegen = X86AssemblyCodegen::with_options(
+            declare_and_compute_program(Operator::Multiply),
+            options,
+        );
+
+        // when
+        let (instructions, statement_ranges) = codegen.generate_with_statement_ranges().unwrap();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov eax, dword [rbp - 4]",
+                "mov ecx, eax",
+                "xor ecx, 3",
+                "sar ecx, 31",
+                "xor ecx, 0x7FFFFFFF",
+                "imul eax, 3",
+                "cmovo eax, ecx",
+                "mov dword [rbp - 8], eax",
+                "mov eax, dword [rbp - 8]",
+                "mov ecx, 0x7FFFFFFF",
+                "neg eax",
+                "cmovo eax, ecx",
+                "mov dword [rbp - 12], eax",
+            ],
+            instructions[statement_ranges[1].start..statement_ranges[2].end]
+        );
+        assert!(!instructions.contains(&"ud2".to_string()));
+    }
+
+    #[test]
+    fn emit_casts_of_runtime_values() {
+        // given
+        let cast = |target| Expression::Cast {
+            expression: Box::new(Expression::VariableAccess {
+                name: "x".to_string(),
+                location: (0, 0),
+            }),
+            target,
+        };
+        let program = Program {
+            statements: vec![
+                Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: -1 },
+                },
+                Statement::VariableDeclaration {
+                    name: "y".to_string(),
+                    value: cast(Type::I64),
+                },
+                Statement::Expression(cast(Type::I32)),
+            ],
+            tail: None,
+        };
+
+        let mut codegen = X86AssemblyCodegen::new(program);
+
+        // when
+        let (instructions, statement_ranges) = codegen.generate_with_statement_ranges().unwrap();
+
+        // then
+        assert_eq!(
+            vec![
+                "mov eax, dword [rbp - 4]",
+                "movsxd rax, eax",
+                "mov dword [rbp - 8], eax",
+                "mov eax, dword [rbp - 4]",
+            ],
+            instructions[statement_ranges[1].start..statement_ranges[2].end]
+        );
+    }
 }
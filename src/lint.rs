@@ -0,0 +1,134 @@
+use std::fmt;
+
+use crate::ast::{Program, Statement};
+
+// this pass looks for code that compiles but is almost certainly a mistake, like
+// an expression statement without side effects, whose value is thrown away:
+// 1 + 2;
+// nothing is changed, every finding is only reported as a warning
+pub struct Lint<'a> {
+    source: &'a str,
+    program: &'a Program,
+    statement_locations: &'a [(usize, usize)],
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    message: String,
+    // (line, column) of the start of the statement, the column is 0-based like in parse errors
+    location: (usize, usize),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}:{}",
+            self.message, self.location.0, self.location.1
+        )
+    }
+}
+
+impl<'a> Lint<'a> {
+    pub fn new(
+        source: &'a str,
+        program: &'a Program,
+        statement_locations: &'a [(usize, usize)],
+    ) -> Self {
+        Self {
+            source,
+            program,
+            statement_locations,
+        }
+    }
+
+    pub fn run(self) -> Vec<Warning> {
+        self.program
+            .statements
+            .iter()
+            .zip(self.statement_locations)
+            .filter_map(|(statement, location)| match statement {
+                Statement::Expression(expression) if !expression.contains_call() => Some(Warning {
+                    message: format!("Unused result of {}", expression),
+                    location: *location,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // optional, because the compiler itself does not care how a line is indented,
+    // reports every line whose indentation has both tabs and spaces
+    pub fn mixed_indentation(&self) -> Vec<Warning> {
+        self.source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                let indentation: Vec<char> = line
+                    .chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .collect();
+
+                indentation.contains(&' ') && indentation.contains(&'\t')
+            })
+            .map(|(index, _)| Warning {
+                message: "Indentation mixes tabs and spaces".to_string(),
+                location: (index + 1, 0),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::{parser::Parser, tokenizer::Tokenizer};
+
+    use super::*;
+
+    #[test]
+    fn warn_about_unused_result_without_side_effects() {
+        // given
+        let source = "let x = 1;\n1 + 2; f();\n  x < 2;";
+        let mut parser = Parser::new(Tokenizer::new(source).tokenize());
+        let program = parser.parse();
+
+        let lint = Lint::new(source, &program, parser.get_statement_locations());
+
+        // when
+        let result: Vec<String> = lint.run().iter().map(Warning::to_string).collect();
+
+        // then
+        assert_eq!(
+            vec![
+                "Unused result of 1 + 2 at 2:0",
+                "Unused result of x < 2 at 3:2"
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn warn_about_indentation_mixing_tabs_and_spaces() {
+        // given
+        let mixed_source = "let x = 1;\n\t print_int(x);\n\tprint_int(x);\n";
+        let consistent_source = "let x = 1;\n\tprint_int(x);\n    print_int(x);\n";
+        let program = Program::default();
+
+        // when
+        let mixed_result: Vec<String> = Lint::new(mixed_source, &program, &[])
+            .mixed_indentation()
+            .iter()
+            .map(Warning::to_string)
+            .collect();
+        let consistent_result = Lint::new(consistent_source, &program, &[]).mixed_indentation();
+
+        // then
+        assert_eq!(
+            vec!["Indentation mixes tabs and spaces at 2:0"],
+            mixed_result
+        );
+        assert_eq!(Vec::<Warning>::new(), consistent_result);
+    }
+}
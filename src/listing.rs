@@ -0,0 +1,175 @@
+use std::{fmt::Write, ops::Range};
+
+use crate::{
+    codegen::Instruction,
+    passes::{GroupedProgram, Pass},
+};
+
+// a listing shows every source statement together with what it becomes after every pass
+// and in the final assembly. the compiler records the program after each pass it runs and
+// hands over the instructions it writes out, so the listing shows what actually gets assembled
+pub struct Listing<'a> {
+    source: &'a str,
+    // the source statements, followed by the tail when the program has one
+    spans: Vec<Range<usize>>,
+    stages: Vec<(Pass, GroupedProgram)>,
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(
+        source: &'a str,
+        statement_spans: &[Range<usize>],
+        tail_span: Option<Range<usize>>,
+    ) -> Self {
+        Self {
+            source,
+            spans: statement_spans.iter().cloned().chain(tail_span).collect(),
+            stages: vec![],
+        }
+    }
+
+    pub fn record(&mut self, pass: Pass, program: &GroupedProgram) {
+        self.stages.push((pass, program.clone()));
+    }
+
+    // the ranges are the ones of the lowered statements followed by the one of the tail,
+    // as codegen returns them for the program the last pass produced
+    pub fn generate(
+        &self,
+        program: &GroupedProgram,
+        instructions: &[Instruction],
+        ranges: &[Range<usize>],
+    ) -> String {
+        let mut ranges = ranges.iter();
+
+        let mut listing = String::new();
+        for (index, span) in self.spans.iter().enumerate() {
+            let is_tail = program.tail.is_some() && index == self.spans.len() - 1;
+
+            if !listing.is_empty() {
+                writeln!(listing).unwrap();
+            }
+
+            writeln!(listing, "{}", &self.source[span.clone()]).unwrap();
+            for (pass, stage) in &self.stages {
+                writeln!(listing, "    {}:", Self::stage_title(*pass)).unwrap();
+                for statement in &stage.groups[index] {
+                    writeln!(listing, "        {:?}", statement).unwrap();
+                }
+                if let (true, Some(tail)) = (is_tail, &stage.tail) {
+                    writeln!(listing, "        {:?}", tail).unwrap();
+                }
+            }
+
+            writeln!(listing, "    assembly:").unwrap();
+            // every lowered statement has its own range of instructions, the tail also has one
+            let range_count = program.groups[index].len() + usize::from(is_tail);
+            for range in ranges.by_ref().take(range_count) {
+                for instruction in &instructions[range.clone()] {
+                    writeln!(listing, "        {}", instruction).unwrap();
+                }
+            }
+        }
+
+        listing
+    }
+
+    fn stage_title(pass: Pass) -> &'static str {
+        match pass {
+            Pass::PartialEvaluate => "partial evaluation",
+            Pass::RemoveComplexOperands => "remove complex operands",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        arithmetic::ArithmeticMode,
+        codegen::{CodegenOptions, X86AssemblyCodegen},
+        parser::Parser,
+        peephole::PeepholeOptimizer,
+        tokenizer::Tokenizer,
+    };
+
+    use super::*;
+
+    // runs the passes, codegen and the peephole optimizer the way the compiler does
+    fn generate_listing(source: &str, options: CodegenOptions) -> String {
+        let mut parser = Parser::new(Tokenizer::new(source).tokenize());
+        let program = parser.parse();
+        let mut listing =
+            Listing::new(source, parser.get_statement_spans(), parser.get_tail_span());
+
+        let mut program = GroupedProgram::from(program);
+        for pass in Pass::DEFAULT_ORDER {
+            program = pass.run(program, ArithmeticMode::default());
+            listing.record(pass, &program);
+        }
+
+        let mut codegen = X86AssemblyCodegen::with_options(program.clone().into_program(), options);
+        let (instructions, ranges) = codegen.generate_with_statement_ranges().unwrap();
+        let (instructions, ranges) = PeepholeOptimizer::new(instructions).run_with_ranges(ranges);
+
+        listing.generate(&program, &instructions, &ranges)
+    }
+
+    #[test]
+    fn group_folded_statement_with_its_instruction() {
+        // given
+        let source = "let x = 1 + 2;";
+
+        // when
+        let result = generate_listing(source, CodegenOptions::default());
+
+        // then
+        assert_eq!(
+            r#"let x = 1 + 2;
+    partial evaluation:
+        VariableDeclaration { name: "x", value: Constant { value: 3 } }
+    remove complex operands:
+        VariableDeclaration { name: "x", value: Constant { value: 3 } }
+    assembly:
+        mov dword [rbp - 4], 3
+"#,
+            result
+        );
+    }
+
+    #[test]
+    fn list_tail_with_the_instructions_returning_it() {
+        // given
+        let source = "let x = 0; x";
+        let options = CodegenOptions {
+            omit_frame_pointer: true,
+            ..CodegenOptions::default()
+        };
+
+        // when
+        let result = generate_listing(source, options);
+
+        // then
+        assert_eq!(
+            r#"let x = 0;
+    partial evaluation:
+        VariableDeclaration { name: "x", value: Constant { value: 0 } }
+    remove complex operands:
+        VariableDeclaration { name: "x", value: Constant { value: 0 } }
+    assembly:
+        mov dword [rsp - 4], 0
+
+x
+    partial evaluation:
+        VariableAccess { name: "x", location: (1, 11) }
+    remove complex operands:
+        VariableAccess { name: "x", location: (1, 11) }
+    assembly:
+        movsxd rax, dword [rsp - 4]
+        ret
+"#,
+            result
+        );
+    }
+}
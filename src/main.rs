@@ -1,27 +1,74 @@
 use std::fs::File;
 
-use crate::partial_evaluator::PartialEvaluator;
-use crate::remove_complex_operands::RemoveComplexOperandsPass;
-use parser::Parser;
+use crate::arithmetic::ArithmeticMode;
+use crate::codegen::{CodegenError, CodegenOptions};
+use crate::lint::Lint;
+use crate::listing::Listing;
+use crate::passes::{GroupedProgram, Pass};
+use crate::peephole::PeepholeOptimizer;
+use crate::resolver::Resolver;
+use crate::target::Target;
+use crate::timings::Timings;
+use parser::{ParseError, Parser};
 use std::env;
 use std::env::Args;
 use std::fs;
+use std::io::ErrorKind;
 use std::io::Write;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
 use tokenizer::Tokenizer;
 
+mod arithmetic;
 mod ast;
 mod codegen;
+mod lint;
+mod listing;
 mod parser;
 mod partial_evaluator;
+mod passes;
+mod peephole;
 mod remove_complex_operands;
+mod resolver;
+mod target;
+mod timings;
 mod tokenizer;
 
+enum Source {
+    File(PathBuf),
+    // the whole program is given on the command line with --expr
+    Inline(String),
+}
+
 struct CompileOptions {
-    source_path: PathBuf,
+    source: Source,
     output_path: PathBuf,
+    // the intermediate files, named after output_path unless given explicitly
+    asm_path: PathBuf,
+    object_path: PathBuf,
+    listing_path: Option<PathBuf>,
+    map_path: Option<PathBuf>,
+    arithmetic_mode: ArithmeticMode,
+    // stop after assembling, output_path then names the object file
+    emit_object: bool,
+    // stop after the front end, only reporting errors
+    check_only: bool,
+    codegen_options: CodegenOptions,
     compile_runtime: bool,
+    // a prebuilt runtime replaces the one compiled from runtime.c, e.g. when cross compiling
+    runtime_object_path: PathBuf,
+    target: Target,
+    link_static: bool,
+    // check the generated instructions for codegen mistakes before assembling them
+    verify_asm: bool,
+    // also warn about lines indented with both tabs and spaces
+    lint_indentation: bool,
+    // print a summary of the generated code, see CodegenStats
+    print_stats: bool,
+    // `-` writes the timings to stdout
+    timings_path: Option<PathBuf>,
+    // the passes run between the resolver and codegen, in order
+    passes: Vec<Pass>,
 }
 
 impl From<Args> for CompileOptions {
@@ -29,91 +76,457 @@ impl From<Args> for CompileOptions {
         let args: Vec<String> = value.collect();
 
         if args.len() < 2 {
-            eprintln!("Usage: yep <filename> [-o <output_filename>]");
+            eprintln!(
+                "Usage: yep fmt <filename> [--check|-]\n       yep <filename>|--expr <code> [-o <output_filename>] [--asm-out <asm_filename>] [--obj-out <object_filename>] [--listing <listing_filename>] [--map <map_filename>] [--arith wrap|check|saturate] [--passes <manifest_filename>] [--emit-obj] [--check] [--no-red-zone] [--profile-labels] [--globals] [--omit-frame-pointer] [--no-ident] [--cet] [--static] [--verify-asm] [--lint-indent] [--stats] [--runtime-obj <runtime_object>] [--dump-timings-json <timings_filename>|-]"
+            );
             panic!();
         };
 
         let mut args = args.into_iter().skip(1).peekable();
-        let source_path = PathBuf::from(args.next().unwrap().as_str());
-        if !source_path.is_file() {
-            panic!("Only files can be compiled");
-        };
+        let source_path = args.next_if(|arg| !arg.starts_with('-')).map(PathBuf::from);
+
+        let mut inline_source = None;
+        let mut output_path = None;
+        let mut asm_path = None;
+        let mut object_path = None;
+        let mut listing_path = None;
+        let mut map_path = None;
+        let mut arithmetic_mode = ArithmeticMode::default();
+        let mut emit_object = false;
+        let mut check_only = false;
+        let mut codegen_options = CodegenOptions::default();
+        let mut link_static = false;
+        let mut verify_asm = false;
+        let mut lint_indentation = false;
+        let mut print_stats = false;
+        let mut runtime_object_path = None;
+        let mut timings_path = None;
+        let mut passes = Pass::DEFAULT_ORDER.to_vec();
 
-        let output_path = if let Some(flag) = args.next() {
-            if flag == "-o" {
-                if args.peek().is_none() {
-                    eprintln!("-o flag provided with no value");
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "-o" => {
+                    output_path = Some(PathBuf::from(Self::consume_flag_value(&flag, &mut args)))
+                }
+                "--asm-out" => {
+                    asm_path = Some(PathBuf::from(Self::consume_flag_value(&flag, &mut args)))
+                }
+                "--obj-out" => {
+                    object_path = Some(PathBuf::from(Self::consume_flag_value(&flag, &mut args)))
+                }
+                "--emit-obj" => emit_object = true,
+                "--check" => check_only = true,
+                "--no-red-zone" => codegen_options.use_red_zone = false,
+                "--profile-labels" => codegen_options.emit_statement_labels = true,
+                "--globals" => codegen_options.constant_globals = true,
+                "--omit-frame-pointer" => codegen_options.omit_frame_pointer = true,
+                "--no-ident" => codegen_options.emit_ident = false,
+                "--cet" => codegen_options.emit_endbranch = true,
+                "--static" => link_static = true,
+                "--verify-asm" => verify_asm = true,
+                "--lint-indent" => lint_indentation = true,
+                "--stats" => print_stats = true,
+                "--runtime-obj" => {
+                    runtime_object_path =
+                        Some(PathBuf::from(Self::consume_flag_value(&flag, &mut args)))
+                }
+                "--expr" => inline_source = Some(Self::consume_flag_value(&flag, &mut args)),
+                "--dump-timings-json" => {
+                    timings_path = Some(PathBuf::from(Self::consume_flag_value(&flag, &mut args)))
+                }
+                "--listing" => {
+                    listing_path = Some(PathBuf::from(Self::consume_flag_value(&flag, &mut args)))
+                }
+                "--map" => {
+                    map_path = Some(PathBuf::from(Self::consume_flag_value(&flag, &mut args)))
+                }
+                "--passes" => {
+                    let manifest_path = Self::consume_flag_value(&flag, &mut args);
+                    let manifest = fs::read_to_string(&manifest_path).unwrap_or_else(|error| {
+                        panic!("Cannot read pass manifest {}: {}", manifest_path, error)
+                    });
+                    passes = Pass::parse_manifest(&manifest).unwrap_or_else(|error| {
+                        eprintln!("{}", error);
+                        panic!();
+                    });
+                }
+                "--arith" => {
+                    let value = Self::consume_flag_value(&flag, &mut args);
+                    arithmetic_mode = value.parse().unwrap_or_else(|error| {
+                        eprintln!("{}", error);
+                        panic!();
+                    });
+                }
+                _ => {
+                    eprintln!("Unknown flag provided: {}", flag);
                     panic!();
                 }
+            }
+        }
 
-                PathBuf::from(args.next().unwrap().as_str())
-            } else {
-                eprintln!("Unknown flag provided: {}", flag);
+        let source = match (source_path, inline_source) {
+            (Some(source_path), None) => {
+                if !source_path.is_file() {
+                    panic!("Only files can be compiled");
+                }
+                Source::File(source_path)
+            }
+            (None, Some(code)) => Source::Inline(code),
+            _ => {
+                eprintln!("Expected either a source file or --expr, but not both");
                 panic!();
             }
-        } else {
-            source_path.with_extension("")
         };
 
+        let output_path = output_path.unwrap_or_else(|| {
+            let base_path = match &source {
+                Source::File(source_path) => source_path.clone(),
+                Source::Inline(_) => PathBuf::from("expr"),
+            };
+
+            if emit_object {
+                base_path.with_extension("o")
+            } else {
+                base_path.with_extension("")
+            }
+        });
+        let asm_path = asm_path.unwrap_or_else(|| output_path.with_extension("asm"));
+        let object_path = object_path.unwrap_or_else(|| {
+            if emit_object {
+                output_path.clone()
+            } else {
+                output_path.with_extension("o")
+            }
+        });
+
         Self {
-            source_path,
+            source,
             output_path,
-            compile_runtime: true,
+            asm_path,
+            object_path,
+            listing_path,
+            map_path,
+            arithmetic_mode,
+            emit_object,
+            check_only,
+            // folding and the emitted instructions have to overflow the same way
+            codegen_options: CodegenOptions {
+                arithmetic_mode,
+                ..codegen_options
+            },
+            compile_runtime: runtime_object_path.is_none(),
+            runtime_object_path: runtime_object_path.unwrap_or_else(|| PathBuf::from("runtime.o")),
+            target: Target::host(),
+            link_static,
+            verify_asm,
+            lint_indentation,
+            print_stats,
+            timings_path,
+            passes,
+        }
+    }
+}
+
+impl CompileOptions {
+    fn consume_flag_value(flag: &str, args: &mut impl Iterator<Item = String>) -> String {
+        match args.next() {
+            Some(value) => value,
+            None => {
+                eprintln!("{} flag provided with no value", flag);
+                panic!();
+            }
         }
     }
 }
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("fmt") {
+        format_file(env::args().skip(2).collect());
+        return;
+    }
+
     let compile_options = CompileOptions::from(env::args());
 
-    let source = fs::read_to_string(&compile_options.source_path).unwrap();
+    let source = match &compile_options.source {
+        Source::File(source_path) => {
+            println!("Compiling {}", source_path.display());
+            fs::read_to_string(source_path).unwrap()
+        }
+        Source::Inline(code) => {
+            println!("Compiling inline expression");
+            code.clone()
+        }
+    };
+
+    let mut timings = Timings::default();
 
-    println!("Compiling {}", compile_options.source_path.display());
-    let tokens = Tokenizer::new(source).tokenize();
-    let program = Parser::new(tokens).parse();
-    let program = PartialEvaluator::new(program).evaluate();
-    let program = RemoveComplexOperandsPass::new(program).run();
+    let tokens = timings.measure("tokenize", || Tokenizer::new(&source).tokenize());
+    timings.token_count = tokens.len();
+    let (program, statement_spans, statement_locations, tail_span) =
+        timings.measure("parse", || {
+            let mut parser = Parser::new(tokens);
+            let program = parser.parse();
+            if !parser.get_errors().is_empty() {
+                report_parse_errors(parser.get_errors());
+            }
 
-    let mut codegen = codegen::X86AssemblyCodegen::new(program);
-    let instructions = codegen.generate();
+            (
+                program,
+                parser.get_statement_spans().to_vec(),
+                parser.get_statement_locations().to_vec(),
+                parser.get_tail_span(),
+            )
+        });
+    timings.statement_count = program.statements.len();
+    let program = timings.measure("resolve", || Resolver::new(program).resolve());
 
-    let asm_path = compile_options.output_path.with_extension("asm");
+    let lint = Lint::new(&source, &program, &statement_locations);
+    let indentation_warnings = if compile_options.lint_indentation {
+        lint.mixed_indentation()
+    } else {
+        vec![]
+    };
+    for warning in indentation_warnings.into_iter().chain(lint.run()) {
+        eprintln!("warning: {}", warning);
+    }
 
-    let mut file = File::create(&asm_path).unwrap();
+    if compile_options.check_only {
+        return;
+    }
+
+    let mut listing = compile_options
+        .listing_path
+        .as_ref()
+        .map(|_| Listing::new(&source, &statement_spans, tail_span));
+
+    Pass::check_lowers_intrinsics(&compile_options.passes, &program).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        panic!();
+    });
+
+    let mut program = GroupedProgram::from(program);
+    for pass in &compile_options.passes {
+        program = timings.measure(pass.name(), || {
+            pass.run(program, compile_options.arithmetic_mode)
+        });
+        if let Some(listing) = &mut listing {
+            listing.record(*pass, &program);
+        }
+    }
+
+    let mut codegen = codegen::X86AssemblyCodegen::with_options(
+        program.clone().into_program(),
+        compile_options.codegen_options,
+    );
+    let (instructions, statement_ranges) = timings
+        .measure("codegen", || codegen.generate_with_statement_ranges())
+        .unwrap_or_else(|error| report_codegen_error(&error));
+    let (instructions, statement_ranges) = timings.measure("peephole", || {
+        PeepholeOptimizer::new(instructions).run_with_ranges(statement_ranges)
+    });
+    timings.instruction_count = instructions.len();
+
+    if let (Some(listing), Some(listing_path)) = (&listing, &compile_options.listing_path) {
+        let contents = listing.generate(&program, &instructions, &statement_ranges);
+        fs::write(listing_path, contents).unwrap();
+    }
+
+    if compile_options.print_stats {
+        println!("{}", codegen.stats(&instructions));
+    }
+
+    if compile_options.verify_asm {
+        codegen::verify_instructions(&instructions)
+            .unwrap_or_else(|error| report_codegen_error(&error));
+    }
+
+    if let Some(map_path) = &compile_options.map_path {
+        let labels = codegen::collect_labels(&instructions);
+        fs::write(map_path, labels.join("\n")).unwrap();
+    }
+
+    let asm_path = &compile_options.asm_path;
+
+    let mut file = File::create(asm_path).unwrap();
     for instruction in instructions {
         writeln!(file, "{}", instruction).unwrap();
     }
 
-    if compile_options.compile_runtime {
-        Command::new("gcc")
-            .args(["-c", "runtime.c", "-o", "runtime.o"])
-            .spawn()
-            .expect("failed to compile runtime");
-    }
-
-    let object_path = compile_options.output_path.with_extension("o");
-    Command::new("nasm")
-        .args(vec![
-            "-f",
-            "elf64",
-            &asm_path.display().to_string(),
-            "-o",
-            &object_path.display().to_string(),
-        ])
-        .output()
-        .expect("failed to compile");
-
-    let program_path = compile_options.output_path;
-    let gcc_output = Command::new("gcc")
-        .args(vec![
-            &object_path.display().to_string(),
-            "runtime.o",
-            "-o",
-            &program_path.display().to_string(),
-        ])
-        .output()
-        .expect("failed to compile");
+    let object_path = &compile_options.object_path;
+    timings.measure("assemble", || {
+        assemble(asm_path, object_path, compile_options.target)
+    });
+
+    if !compile_options.emit_object {
+        if compile_options.compile_runtime {
+            run_tool(Command::new("gcc").args(compile_options.target.runtime_compile_args()));
+        }
+
+        timings.measure("link", || {
+            link(
+                object_path,
+                &compile_options.runtime_object_path,
+                &compile_options.output_path,
+                compile_options.target,
+                compile_options.link_static,
+            )
+        });
+    }
+
+    if let Some(timings_path) = &compile_options.timings_path {
+        if timings_path == Path::new("-") {
+            println!("{}", timings.to_json());
+        } else {
+            fs::write(timings_path, timings.to_json()).unwrap();
+        }
+    }
+}
+
+// yep fmt <filename> rewrites the file in place, --check only reports whether it would change
+// and - prints the formatted source instead
+fn format_file(args: Vec<String>) {
+    let Some((source_path, flags)) = args.split_first() else {
+        eprintln!("Usage: yep fmt <filename> [--check|-]");
+        process::exit(1);
+    };
+
+    let source = fs::read_to_string(source_path).unwrap();
+    let formatted = format_source(source.clone());
+
+    match flags.first().map(String::as_str) {
+        None => fs::write(source_path, formatted).unwrap(),
+        Some("-") => print!("{}", formatted),
+        Some("--check") => {
+            if formatted != source {
+                eprintln!("{} is not formatted", source_path);
+                process::exit(1);
+            }
+        }
+        Some(flag) => {
+            eprintln!("Unknown flag provided: {}", flag);
+            process::exit(1);
+        }
+    }
+}
+
+// comments are not part of the ast, so they are dropped
+fn format_source(source: String) -> String {
+    let program = Parser::parse_str(&source).unwrap_or_else(|errors| report_parse_errors(&errors));
+
+    ast::format_program(&program)
+}
+
+// the parser skips what it cannot parse, so every error is listed before giving up
+fn report_parse_errors(errors: &[ParseError]) -> ! {
+    for error in errors {
+        eprintln!("{}", error);
+    }
+    panic!("Could not parse the program");
+}
+
+// unlike the parser, codegen stops at the first error
+fn report_codegen_error(error: &CodegenError) -> ! {
+    eprintln!("{}", error);
+    panic!("Could not generate code for the program");
+}
+
+fn assemble(asm_path: &Path, object_path: &Path, target: Target) {
+    run_tool(
+        Command::new("nasm")
+            .args(target.assembler_args())
+            .args(vec![
+                &asm_path.display().to_string(),
+                "-o",
+                &object_path.display().to_string(),
+            ]),
+    );
+}
+
+fn link(
+    object_path: &Path,
+    runtime_object_path: &Path,
+    program_path: &Path,
+    target: Target,
+    link_static: bool,
+) {
+    let link_args = target.link_args(link_static).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let mut gcc = Command::new("gcc");
+    gcc.args(vec![
+        &object_path.display().to_string(),
+        &runtime_object_path.display().to_string(),
+        "-o",
+        &program_path.display().to_string(),
+    ]);
+    gcc.args(link_args);
+
+    let gcc_output = run_tool(&mut gcc);
 
     let _stdout = String::from_utf8_lossy(&gcc_output.stdout);
     let _stderr = String::from_utf8_lossy(&gcc_output.stderr);
 }
+
+// the external tools are the most common thing to be missing on a first run
+fn run_tool(command: &mut Command) -> Output {
+    command.output().unwrap_or_else(|error| {
+        let tool = command.get_program().to_string_lossy().to_string();
+        eprintln!("{}", describe_tool_error(&tool, error.kind()));
+        process::exit(1);
+    })
+}
+
+fn describe_tool_error(tool: &str, error_kind: ErrorKind) -> String {
+    match error_kind {
+        ErrorKind::NotFound => format!(
+            "{} not found, please install it, e.g. with `sudo apt install {}`",
+            tool, tool
+        ),
+        _ => format!("Failed to run {}: {}", tool, error_kind),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn describe_missing_tool_with_install_hint() {
+        // given
+        let error_kinds = [ErrorKind::NotFound, ErrorKind::PermissionDenied];
+
+        // when
+        let result: Vec<String> = error_kinds
+            .into_iter()
+            .map(|error_kind| describe_tool_error("nasm", error_kind))
+            .collect();
+
+        // then
+        assert_eq!(
+            vec![
+                "nasm not found, please install it, e.g. with `sudo apt install nasm`",
+                "Failed to run nasm: permission denied",
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn format_source_in_canonical_form() {
+        // given
+        let source = "let a=1;let b =a*(2+3);\n/* the result */\nprint_int(  b)  ;\nb".to_string();
+
+        // when
+        let result = format_source(source);
+
+        // then
+        assert_eq!(
+            "let a = 1;\nlet b = a * (2 + 3);\nprint_int(b);\nb\n",
+            result
+        );
+    }
+}
@@ -1,65 +1,272 @@
+use std::{fmt, ops::Range};
+
 use crate::{
-    ast::{Expression, Program, Statement},
-    tokenizer::{Keyword, Token, TokenType},
+    ast::{Expression, Operator, Program, Statement, Type},
+    tokenizer::{Keyword, Token, TokenType, Tokenizer},
 };
 
+// tokens that can begin a statement, anything else at the start of one is skipped
+const STATEMENT_START: [TokenType; 12] = [
+    TokenType::Keyword(Keyword::Let),
+    TokenType::Keyword(Keyword::Print),
+    TokenType::Identifier,
+    TokenType::Number,
+    TokenType::Keyword(Keyword::True),
+    TokenType::Keyword(Keyword::False),
+    // only to report that floats are not supported
+    TokenType::Float,
+    TokenType::String,
+    TokenType::Char,
+    TokenType::ParenthesesLeft,
+    TokenType::Minus,
+    TokenType::Plus,
+];
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    expected: Vec<TokenType>,
+    found: TokenType,
+    location: (usize, usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected: Vec<String> = self.expected.iter().map(|t| t.to_string()).collect();
+
+        write!(
+            f,
+            "Expected one of {}, but found {} at {}:{}",
+            expected.join(", "),
+            self.found,
+            self.location.0,
+            self.location.1
+        )
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     cursor: usize,
+    // source byte range of every parsed statement
+    statement_spans: Vec<Range<usize>>,
+    // (line, column) of the first token of every parsed statement
+    statement_locations: Vec<(usize, usize)>,
+    // source byte range of the tail expression, when the program has one
+    tail_span: Option<Range<usize>>,
+    // errors the parser recovered from, the program is only valid when there are none
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, cursor: 0 }
+        Self {
+            tokens,
+            cursor: 0,
+            statement_spans: vec![],
+            statement_locations: vec![],
+            tail_span: None,
+            errors: vec![],
+        }
+    }
+
+    // tokenizes and parses in one go, the errors are the ones the parser recovered from
+    pub fn parse_str(source: &str) -> Result<Program, Vec<ParseError>> {
+        let mut parser = Self::new(Tokenizer::new(source).tokenize());
+        let program = parser.parse();
+
+        if parser.errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(parser.errors)
+        }
     }
 
     pub fn parse(&mut self) -> Program {
         let mut statements = vec![];
+        let mut tail = None;
 
         while !self.is_at_end() {
-            statements.push(self.parse_statement());
+            if let Err(error) = self.expect_statement_start() {
+                self.errors.push(error);
+                self.skip_to_next_statement();
+                continue;
+            }
+
+            let first_token = self.tokens[self.cursor].clone();
+            let parsed_statements = self.parse_statement();
+            let last_token = self.get_previous_token();
+
+            let span = first_token.get_offset()..last_token.get_offset() + last_token.get_length();
+
+            if let [Statement::Expression(expression)] = parsed_statements.as_slice() {
+                if last_token.get_type() != &TokenType::Semicolon {
+                    tail = Some(expression.clone());
+                    self.tail_span = Some(span);
+                    break;
+                }
+            }
+
+            // statements parsed together share the span, so spans still match statements by index
+            for statement in parsed_statements {
+                statements.push(statement);
+                self.statement_spans.push(span.clone());
+                self.statement_locations.push(first_token.get_location());
+            }
+        }
+
+        Program { statements, tail }
+    }
+
+    pub fn get_statement_spans(&self) -> &[Range<usize>] {
+        &self.statement_spans
+    }
+
+    pub fn get_statement_locations(&self) -> &[(usize, usize)] {
+        &self.statement_locations
+    }
+
+    pub fn get_tail_span(&self) -> Option<Range<usize>> {
+        self.tail_span.clone()
+    }
+
+    pub fn get_errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    // the tokens not consumed yet, always ending with Eof
+    pub fn remaining(&self) -> &[Token] {
+        &self.tokens[self.cursor..]
+    }
+
+    fn expect_statement_start(&self) -> Result<(), ParseError> {
+        let current = &self.tokens[self.cursor];
+
+        if STATEMENT_START.contains(current.get_type()) {
+            return Ok(());
         }
 
-        Program { statements }
+        Err(ParseError {
+            expected: STATEMENT_START.to_vec(),
+            found: current.get_type().clone(),
+            location: current.get_location(),
+        })
     }
 
-    fn parse_statement(&mut self) -> Statement {
+    // skips past the next semicolon, or up to a keyword that starts a statement
+    fn skip_to_next_statement(&mut self) {
+        while !self.is_at_end() {
+            let current_type = self.tokens[self.cursor].get_type();
+
+            if matches!(
+                current_type,
+                TokenType::Keyword(Keyword::Let) | TokenType::Keyword(Keyword::Print)
+            ) {
+                return;
+            }
+
+            self.cursor += 1;
+
+            if current_type == &TokenType::Semicolon {
+                return;
+            }
+        }
+    }
+
+    fn parse_statement(&mut self) -> Vec<Statement> {
+        if self
+            .consume_if_matched(vec![TokenType::Keyword(Keyword::Print)])
+            .is_some()
+        {
+            return vec![self.parse_print()];
+        }
+
         self.parse_variable_declaration()
     }
 
-    fn parse_variable_declaration(&mut self) -> Statement {
-        if let Some(_) = self.consume_if_matched(vec![TokenType::Keyword(Keyword::Let)]) {
-            let identifier = self.consume_required(TokenType::Identifier);
+    // `print x;` is a shorthand for `print_int(x);`, so it needs no support after parsing
+    fn parse_print(&mut self) -> Statement {
+        let value = self.parse_expression();
+        self.consume_required(TokenType::Semicolon);
+
+        Statement::Expression(Expression::Call {
+            name: "print_int".to_string(),
+            args: vec![value],
+        })
+    }
+
+    // `let a, b = 0;` declares every listed variable with a copy of the same initializer
+    fn parse_variable_declaration(&mut self) -> Vec<Statement> {
+        if self
+            .consume_if_matched(vec![TokenType::Keyword(Keyword::Let)])
+            .is_some()
+        {
+            let mut identifiers = vec![self.consume_required(TokenType::Identifier)];
+            while self.consume_if_matched(vec![TokenType::Comma]).is_some() {
+                identifiers.push(self.consume_required(TokenType::Identifier));
+            }
 
             self.consume_required(TokenType::Equals);
 
             let initializer = self.parse_expression();
 
-            self.consume_required(TokenType::Semicolon);
+            self.expect_any(&[TokenType::Semicolon])
+                .unwrap_or_else(|error| panic!("{}", error));
 
-            return Statement::VariableDeclaration {
-                name: identifier.get_literal_value().to_string(),
+            // the initializer is evaluated once, the other variables copy the first one
+            let first = &identifiers[0];
+            let copies = identifiers[1..]
+                .iter()
+                .map(|identifier| Statement::VariableDeclaration {
+                    name: identifier.get_literal_value().to_string(),
+                    value: Expression::VariableAccess {
+                        name: first.get_literal_value().to_string(),
+                        location: first.get_location(),
+                    },
+                });
+
+            return std::iter::once(Statement::VariableDeclaration {
+                name: first.get_literal_value().to_string(),
                 value: initializer,
-            };
+            })
+            .chain(copies)
+            .collect();
         }
 
-        Statement::Expression(self.parse_expression())
+        let mut expression = self.parse_expression();
+        // parentheses around a whole statement group nothing
+        while let Expression::Grouping { expression: inner } = expression {
+            expression = *inner;
+        }
+
+        // the last expression of a program can leave out the semicolon to become its result
+        if !self.is_at_end() {
+            self.consume_required(TokenType::Semicolon);
+        }
+
+        vec![Statement::Expression(expression)]
     }
 
     fn parse_expression(&mut self) -> Expression {
-        self.parse_term()
+        self.parse_binary_op(0)
     }
 
-    fn parse_term(&mut self) -> Expression {
-        let mut expression = self.parse_factor();
+    // operators of the same precedence are folded in a loop, so chains lean to the left,
+    // only an operator that binds tighter starts a nested call
+    fn parse_binary_op(&mut self, min_precedence: u8) -> Expression {
+        let mut expression = self.parse_cast();
+
+        while let Some(operator) = self.peek_binary_operator() {
+            let precedence = operator.precedence();
+            if precedence < min_precedence {
+                break;
+            }
 
-        while let Some(_) = self.consume_if_matched(vec![TokenType::Plus, TokenType::Minus]) {
-            let operator = self.get_previous_token();
-            let rhs = self.parse_factor();
+            self.cursor += 1;
+            let rhs = self.parse_binary_op(precedence + 1);
 
             expression = Expression::BinaryOp {
                 left: Box::new(expression),
-                operator: operator.get_type().into(),
+                operator,
                 right: Box::new(rhs),
             }
         }
@@ -67,43 +274,83 @@ impl Parser {
         expression
     }
 
-    fn parse_factor(&mut self) -> Expression {
+    // like in rust, `as` binds tighter than binary operators, so a * b as i64 is a * (b as i64)
+    fn parse_cast(&mut self) -> Expression {
         let mut expression = self.parse_function_call();
 
-        while let Some(_) = self.consume_if_matched(vec![TokenType::Star, TokenType::Slash]) {
-            let operator = self.get_previous_token();
-            let rhs = self.parse_function_call();
+        while self
+            .consume_if_matched(vec![TokenType::Keyword(Keyword::As)])
+            .is_some()
+        {
+            let type_name = self.consume_required(TokenType::Identifier);
+            let target =
+                Type::try_match_from_name(type_name.get_literal_value()).unwrap_or_else(|| {
+                    let (line, column) = type_name.get_location();
+                    panic!(
+                        "Unknown type {} at {}:{}",
+                        type_name.get_literal_value(),
+                        line,
+                        column
+                    )
+                });
 
-            expression = Expression::BinaryOp {
-                left: Box::new(expression),
-                operator: operator.get_type().into(),
-                right: Box::new(rhs),
-            }
+            expression = Expression::Cast {
+                expression: Box::new(expression),
+                target,
+            };
         }
 
         expression
     }
 
+    fn peek_binary_operator(&self) -> Option<Operator> {
+        match self.tokens[self.cursor].get_type() {
+            token_type @ (TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Star
+            | TokenType::Slash
+            | TokenType::Percent
+            | TokenType::EqualsEquals
+            | TokenType::NotEquals
+            | TokenType::LessThan
+            | TokenType::LessThanEquals
+            | TokenType::GreaterThan
+            | TokenType::GreaterThanEquals) => Some(token_type.into()),
+            _ => None,
+        }
+    }
+
     fn parse_function_call(&mut self) -> Expression {
         let expression = self.parse_unary();
 
-        if let Some(_) = self.consume_if_matched(vec![TokenType::ParenthesesLeft]) {
-            // TODO: handle expressions
-            let variable_access = self.consume_required(TokenType::Identifier);
+        if self
+            .consume_if_matched(vec![TokenType::ParenthesesLeft])
+            .is_some()
+        {
+            let mut args = vec![];
+            if self
+                .consume_if_matched(vec![TokenType::ParenthesesRight])
+                .is_none()
+            {
+                loop {
+                    args.push(self.parse_expression());
 
-            self.consume_required(TokenType::ParenthesesRight);
-            self.consume_required(TokenType::Semicolon);
+                    if self.consume_if_matched(vec![TokenType::Comma]).is_none() {
+                        break;
+                    }
+                }
+
+                self.consume_required(TokenType::ParenthesesRight);
+            }
 
             let function_name = match expression {
-                Expression::VariableAccess { name } => name,
+                Expression::VariableAccess { name, .. } => name,
                 _ => panic!(),
             };
 
             return Expression::Call {
                 name: function_name,
-                args: vec![Expression::VariableAccess {
-                    name: variable_access.get_literal_value().to_string(),
-                }],
+                args,
             };
         }
 
@@ -112,12 +359,29 @@ impl Parser {
 
     fn parse_unary(&mut self) -> Expression {
         if let Some(operator) = self.consume_if_matched(vec![TokenType::Minus]) {
+            // negative literals become constants right away, so they compile even when
+            // the partial evaluator does not run
+            if let Some(number) = self.consume_if_matched(vec![TokenType::Number]) {
+                return Expression::Constant {
+                    value: Self::parse_integer_literal(&number, true),
+                };
+            }
+
             let rhs = self.parse_unary();
 
             return Expression::UnaryOp {
                 operator: operator.get_type().into(),
-                operand: Box::new(rhs)
-            }
+                operand: Box::new(rhs),
+            };
+        }
+
+        if let Some(operator) = self.consume_if_matched(vec![TokenType::Plus]) {
+            let rhs = self.parse_unary();
+
+            return Expression::UnaryOp {
+                operator: operator.get_type().into(),
+                operand: Box::new(rhs),
+            };
         }
 
         self.parse_primary()
@@ -127,24 +391,89 @@ impl Parser {
         if let Some(identifier) = self.consume_if_matched(vec![TokenType::Identifier]) {
             return Expression::VariableAccess {
                 name: identifier.get_literal_value().to_string(),
+                location: identifier.get_location(),
             };
         }
 
         if let Some(number) = self.consume_if_matched(vec![TokenType::Number]) {
             return Expression::Constant {
-                value: number.get_literal_value().parse::<i64>().unwrap(),
+                value: Self::parse_integer_literal(&number, false),
+            };
+        }
+
+        if let Some(boolean) = self.consume_if_matched(vec![
+            TokenType::Keyword(Keyword::True),
+            TokenType::Keyword(Keyword::False),
+        ]) {
+            return Expression::Boolean {
+                value: boolean.get_type() == &TokenType::Keyword(Keyword::True),
+            };
+        }
+
+        // a character is its code point, there is no separate char type
+        if let Some(character) = self.consume_if_matched(vec![TokenType::Char]) {
+            let code_point = character.get_literal_value().chars().next().unwrap();
+
+            return Expression::Constant {
+                value: i64::from(u32::from(code_point)),
             };
         }
 
-        if let Some(_) = self.consume_if_matched(vec![TokenType::ParenthesesLeft]) {
+        if let Some(string) = self.consume_if_matched(vec![TokenType::String]) {
+            return Expression::StringLiteral {
+                value: string.get_literal_value().to_string(),
+            };
+        }
+
+        if let Some(number) = self.consume_if_matched(vec![TokenType::Float]) {
+            let (line, column) = number.get_location();
+            panic!(
+                "Floating point numbers are not supported yet, found {} at {}:{}",
+                number.get_literal_value(),
+                line,
+                column
+            );
+        }
+
+        if self
+            .consume_if_matched(vec![TokenType::ParenthesesLeft])
+            .is_some()
+        {
             let expression = self.parse_expression();
             self.consume_required(TokenType::ParenthesesRight);
 
-            return Expression::Grouping { expression: Box::new(expression) }
+            return Expression::Grouping {
+                expression: Box::new(expression),
+            };
         }
 
-        dbg!(&self.tokens[self.cursor]);
-        panic!("Expected expression");
+        let found = &self.remaining()[0];
+        let (line, column) = found.get_location();
+        panic!(
+            "Expected expression, but found {} at {}:{}",
+            found.get_type(),
+            line,
+            column
+        );
+    }
+
+    // the sign is parsed together with the digits, so that the smallest i64 can be written
+    fn parse_integer_literal(number: &Token, is_negative: bool) -> i64 {
+        let literal = number.get_literal_value();
+        let (radix, digits) = match literal.get(..2) {
+            Some("0x" | "0X") => (16, &literal[2..]),
+            Some("0b" | "0B") => (2, &literal[2..]),
+            _ => (10, literal),
+        };
+        let sign = if is_negative { "-" } else { "" };
+
+        i64::from_str_radix(&format!("{}{}", sign, digits), radix).unwrap_or_else(|error| {
+            let (line, column) = number.get_location();
+            panic!(
+                "Malformed integer literal {}{} at {}:{}, {}",
+                sign, literal, line, column, error
+            )
+        })
     }
 
     fn consume_required(&mut self, required_type: TokenType) -> Token {
@@ -164,6 +493,22 @@ impl Parser {
         current
     }
 
+    fn expect_any(&mut self, wanted: &[TokenType]) -> Result<Token, ParseError> {
+        let current = self.tokens[self.cursor].clone();
+
+        if !wanted.contains(current.get_type()) {
+            return Err(ParseError {
+                expected: wanted.to_vec(),
+                found: current.get_type().clone(),
+                location: current.get_location(),
+            });
+        }
+
+        self.cursor += 1;
+
+        Ok(current)
+    }
+
     fn consume_if_matched(&mut self, wanted: Vec<TokenType>) -> Option<Token> {
         let current = self.tokens[self.cursor].clone();
         let current_type = current.get_type();
@@ -187,3 +532,567 @@ impl Parser {
         self.tokens[self.cursor].get_type() == &TokenType::Eof
     }
 }
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::ast::without_spans;
+
+    use super::*;
+
+    #[test]
+    fn report_expected_alternatives_when_none_matched() {
+        // given
+        let tokens = Tokenizer::new("1 2").tokenize();
+        let mut parser = Parser::new(tokens);
+        parser.consume_required(TokenType::Number);
+
+        // when
+        let result = parser.expect_any(&[TokenType::Plus, TokenType::Semicolon]);
+
+        // then
+        assert_eq!(
+            Err(ParseError {
+                expected: vec![TokenType::Plus, TokenType::Semicolon],
+                found: TokenType::Number,
+                location: (1, 2),
+            }),
+            result
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected one of Semicolon, but found Number at 1:10")]
+    fn report_unexpected_token_in_operator_position() {
+        // given
+        let tokens = Tokenizer::new("let x = 1 2;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when & then
+        parser.parse();
+    }
+
+    #[test]
+    fn recover_from_stray_token_before_statement() {
+        // given
+        let tokens = Tokenizer::new(") let x = 1; ; print x;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        let errors: Vec<String> = parser.get_errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "Expected one of Keyword(Let), Keyword(Print), Identifier, Number, Keyword(True), Keyword(False), Float, String, Char, ParenthesesLeft, Minus, Plus, but found ParenthesesRight at 1:0",
+                "Expected one of Keyword(Let), Keyword(Print), Identifier, Number, Keyword(True), Keyword(False), Float, String, Char, ParenthesesLeft, Minus, Plus, but found Semicolon at 1:13",
+            ],
+            errors
+        );
+        assert_eq!(2, program.statements.len());
+        assert_eq!(
+            Statement::VariableDeclaration {
+                name: "x".to_string(),
+                value: Expression::Constant { value: 1 },
+            },
+            program.statements[0]
+        );
+        assert_eq!(2, parser.get_statement_spans().len());
+    }
+
+    #[test]
+    fn parse_negative_literal_as_constant() {
+        // given
+        let tokens = Tokenizer::new("let x = -5;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            vec![Statement::VariableDeclaration {
+                name: "x".to_string(),
+                value: Expression::Constant { value: -5 },
+            }],
+            program.statements
+        );
+    }
+
+    #[test]
+    fn parse_negated_variable_as_unary_op() {
+        // given
+        let tokens = Tokenizer::new("let y = -x;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            vec![Statement::VariableDeclaration {
+                name: "y".to_string(),
+                value: Expression::UnaryOp {
+                    operator: Operator::Sub,
+                    operand: Box::new(Expression::VariableAccess {
+                        name: "x".to_string(),
+                        location: (1, 9),
+                    }),
+                },
+            }],
+            program.statements
+        );
+    }
+
+    #[test]
+    fn parse_final_expression_without_semicolon_as_tail() {
+        // given
+        let tokens = Tokenizer::new("42").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            Program {
+                statements: vec![],
+                tail: Some(Expression::Constant { value: 42 }),
+            },
+            program
+        );
+        assert_eq!(Some(0..2), parser.get_tail_span());
+    }
+
+    #[test]
+    fn parse_final_expression_with_semicolon_as_statement() {
+        // given
+        let tokens = Tokenizer::new("42;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            Program {
+                statements: vec![Statement::Expression(Expression::Constant { value: 42 })],
+                tail: None,
+            },
+            program
+        );
+    }
+
+    #[test]
+    fn parse_function_call_with_expression_arguments() {
+        // given
+        let tokens = Tokenizer::new("print_int(-5, a * 2);").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            without_spans(&Program {
+                statements: vec![Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![
+                        Expression::Constant { value: -5 },
+                        Expression::BinaryOp {
+                            left: Box::new(Expression::VariableAccess {
+                                name: "a".to_string(),
+                                location: (0, 0),
+                            }),
+                            operator: Operator::Multiply,
+                            right: Box::new(Expression::Constant { value: 2 }),
+                        },
+                    ],
+                })],
+                tail: None,
+            }),
+            without_spans(&program)
+        );
+    }
+
+    #[test]
+    fn parse_program_from_source_string() {
+        // when
+        let result = Parser::parse_str("let x = 1;");
+
+        // then
+        assert_eq!(
+            Ok(Program {
+                statements: vec![Statement::VariableDeclaration {
+                    name: "x".to_string(),
+                    value: Expression::Constant { value: 1 },
+                }],
+                tail: None,
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn report_errors_when_parsing_source_string() {
+        // when
+        let result = Parser::parse_str(") let x = 1;");
+
+        // then
+        assert_eq!(1, result.unwrap_err().len());
+    }
+
+    #[test]
+    fn parse_hand_made_tokens() {
+        // given
+        let tokens = vec![
+            Token::new(TokenType::Keyword(Keyword::Let), "let", (1, 0), 3),
+            Token::new(TokenType::Identifier, "y", (1, 4), 1),
+            Token::new(TokenType::Equals, "=", (1, 6), 1),
+            Token::new(TokenType::Identifier, "x", (1, 8), 1),
+            Token::new(TokenType::Minus, "-", (1, 10), 1),
+            Token::new(TokenType::Number, "3", (1, 12), 1),
+            Token::new(TokenType::Semicolon, ";", (1, 13), 1),
+            Token::new(TokenType::Eof, "", (1, 14), 0),
+        ];
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            Program {
+                statements: vec![Statement::VariableDeclaration {
+                    name: "y".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (1, 8),
+                        }),
+                        operator: Operator::Sub,
+                        right: Box::new(Expression::Constant { value: 3 }),
+                    },
+                }],
+                tail: None,
+            },
+            program
+        );
+    }
+
+    #[test]
+    fn parse_boolean_literals() {
+        // given
+        let tokens = Tokenizer::new("let b = true; false;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            Program {
+                statements: vec![
+                    Statement::VariableDeclaration {
+                        name: "b".to_string(),
+                        value: Expression::Boolean { value: true },
+                    },
+                    Statement::Expression(Expression::Boolean { value: false }),
+                ],
+                tail: None,
+            },
+            program
+        );
+    }
+
+    #[test]
+    fn parse_cast_to_type() {
+        // given
+        let tokens = Tokenizer::new("let y = x as i64 * 2;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            without_spans(&Program {
+                statements: vec![Statement::VariableDeclaration {
+                    name: "y".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(Expression::Cast {
+                            expression: Box::new(Expression::VariableAccess {
+                                name: "x".to_string(),
+                                location: (0, 0),
+                            }),
+                            target: Type::I64,
+                        }),
+                        operator: Operator::Multiply,
+                        right: Box::new(Expression::Constant { value: 2 }),
+                    },
+                }],
+                tail: None,
+            }),
+            without_spans(&program)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown type u8 at 1:13")]
+    fn report_cast_to_unknown_type() {
+        // given
+        let tokens = Tokenizer::new("let y = x as u8;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when & then
+        parser.parse();
+    }
+
+    #[test]
+    fn parse_comparison_with_lower_precedence_than_arithmetic() {
+        // given
+        let tokens = Tokenizer::new("1 + 2 < 3 * 4;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::BinaryOp {
+                    left: Box::new(Expression::Constant { value: 1 }),
+                    operator: Operator::Add,
+                    right: Box::new(Expression::Constant { value: 2 }),
+                }),
+                operator: Operator::Less,
+                right: Box::new(Expression::BinaryOp {
+                    left: Box::new(Expression::Constant { value: 3 }),
+                    operator: Operator::Multiply,
+                    right: Box::new(Expression::Constant { value: 4 }),
+                }),
+            })],
+            program.statements
+        );
+    }
+
+    #[test]
+    fn parse_declaration_of_several_variables() {
+        // given
+        let tokens = Tokenizer::new("let a, b = 0;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            vec![
+                Statement::VariableDeclaration {
+                    name: "a".to_string(),
+                    value: Expression::Constant { value: 0 },
+                },
+                Statement::VariableDeclaration {
+                    name: "b".to_string(),
+                    value: Expression::VariableAccess {
+                        name: "a".to_string(),
+                        location: (1, 4),
+                    },
+                },
+            ],
+            program.statements
+        );
+        assert_eq!(&[0..13, 0..13], parser.get_statement_spans());
+    }
+
+    #[test]
+    fn call_shared_initializer_of_several_variables_once() {
+        // given
+        let tokens = Tokenizer::new("let a, b, c = next();").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        let calls = program
+            .statements
+            .iter()
+            .filter(|statement| {
+                matches!(
+                    statement,
+                    Statement::VariableDeclaration {
+                        value: Expression::Call { .. },
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(1, calls);
+        assert_eq!(
+            Statement::VariableDeclaration {
+                name: "c".to_string(),
+                value: Expression::VariableAccess {
+                    name: "a".to_string(),
+                    location: (1, 4),
+                },
+            },
+            program.statements[2]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected the following token: Identifier, but got Equals instead")]
+    fn report_trailing_comma_in_declaration() {
+        // given
+        let tokens = Tokenizer::new("let a, = 0;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when & then
+        parser.parse();
+    }
+
+    #[test]
+    fn return_tokens_left_after_statement() {
+        // given
+        let tokens = Tokenizer::new("let a = 1; a;").tokenize();
+        let mut parser = Parser::new(tokens);
+        parser.parse_statement();
+
+        // when
+        let result: Vec<&TokenType> = parser
+            .remaining()
+            .iter()
+            .map(|token| token.get_type())
+            .collect();
+
+        // then
+        assert_eq!(
+            vec![
+                &TokenType::Identifier,
+                &TokenType::Semicolon,
+                &TokenType::Eof
+            ],
+            result
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected expression, but found Semicolon at 1:8")]
+    fn report_missing_expression_with_location() {
+        // given
+        let tokens = Tokenizer::new("let a = ;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when & then
+        parser.parse();
+    }
+
+    #[test]
+    fn parse_print_as_print_int_call() {
+        // given
+        let tokens = Tokenizer::new("print 5; print_int(5);").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        let call = Statement::Expression(Expression::Call {
+            name: "print_int".to_string(),
+            args: vec![Expression::Constant { value: 5 }],
+        });
+        assert_eq!(vec![call.clone(), call], program.statements);
+    }
+
+    #[test]
+    fn unwrap_parenthesized_expression_statement() {
+        // given
+        let tokens = Tokenizer::new("(f(x)); ((f(x)));").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        let call = Statement::Expression(Expression::Call {
+            name: "f".to_string(),
+            args: vec![Expression::VariableAccess {
+                name: "x".to_string(),
+                location: (0, 0),
+            }],
+        });
+        assert_eq!(
+            without_spans(&Program {
+                statements: vec![call.clone(), call],
+                tail: None,
+            }),
+            without_spans(&program)
+        );
+    }
+
+    #[test]
+    fn parse_hex_and_binary_literals() {
+        // given
+        let tokens = Tokenizer::new("let x = 0xFF; let y = -0b1_01; let z = 10;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        let values: Vec<Expression> = program
+            .statements
+            .into_iter()
+            .map(|statement| match statement {
+                Statement::VariableDeclaration { value, .. } => value,
+                Statement::Expression(expression) => expression,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                Expression::Constant { value: 255 },
+                Expression::Constant { value: -5 },
+                Expression::Constant { value: 10 },
+            ],
+            values
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Malformed integer literal 0b102 at 1:8, invalid digit found in string"
+    )]
+    fn report_malformed_binary_literal() {
+        // given
+        let tokens = Tokenizer::new("let x = 0b102;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when & then
+        parser.parse();
+    }
+
+    #[test]
+    fn parse_unary_plus() {
+        // given
+        let tokens = Tokenizer::new("let x = +5;").tokenize();
+        let mut parser = Parser::new(tokens);
+
+        // when
+        let program = parser.parse();
+
+        // then
+        assert_eq!(
+            vec![Statement::VariableDeclaration {
+                name: "x".to_string(),
+                value: Expression::UnaryOp {
+                    operator: Operator::Add,
+                    operand: Box::new(Expression::Constant { value: 5 }),
+                },
+            }],
+            program.statements
+        );
+    }
+}
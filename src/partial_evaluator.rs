@@ -1,101 +1,216 @@
-use crate::ast::{Expression, Operator, Program, Statement};
+use crate::{
+    arithmetic::ArithmeticMode,
+    ast::{Expression, Operator, Program, Statement, Type},
+};
+
+// calls to this function are replaced with the length of their string literal argument
+pub const LEN_INTRINSIC: &str = "len";
+
+// calls to these are folded when every argument is constant, codegen computes the rest inline
+// picking the smallest or the biggest argument with cmov
+pub const MIN_MAX_INTRINSICS: [&str; 2] = ["min", "max"];
 
 pub struct PartialEvaluator {
     program: Program,
+    arithmetic_mode: ArithmeticMode,
 }
 
 impl PartialEvaluator {
+    #[cfg(test)]
     pub fn new(program: Program) -> Self {
-        Self { program }
+        Self::with_arithmetic_mode(program, ArithmeticMode::default())
+    }
+
+    pub fn with_arithmetic_mode(program: Program, arithmetic_mode: ArithmeticMode) -> Self {
+        Self {
+            program,
+            arithmetic_mode,
+        }
     }
 
-    pub fn evaluate(self) -> Program {
+    pub fn evaluate(mut self) -> Program {
+        let statements = std::mem::take(&mut self.program.statements);
+        let tail = self.program.tail.take();
+
         Program {
-            statements: self
-                .program
-                .statements
-                .iter()
-                .map(|statement| self.evaluate_statement(statement.clone()))
+            statements: statements
+                .into_iter()
+                .map(|statement| self.fold_statement(statement))
                 .collect(),
+            tail: tail.map(|tail| self.fold_expression(tail)),
         }
     }
 
-    fn evaluate_statement(&self, statment: Statement) -> Statement {
+    // folds a single statement, the program passed to the constructor is not used
+    pub fn fold_statement(&self, statment: Statement) -> Statement {
         match statment {
             Statement::Expression(expression) => {
-                Statement::Expression(self.evaluate_expression(expression))
+                Statement::Expression(self.fold_expression(expression))
             }
             Statement::VariableDeclaration { name, value } => Statement::VariableDeclaration {
                 name,
-                value: self.evaluate_expression(value),
+                value: self.fold_expression(value),
             },
         }
     }
 
-    fn evaluate_expression(&self, expression: Expression) -> Expression {
-        match &expression {
-            Expression::UnaryOp { operator, operand } => {
-                let operand = self.evaluate_expression(*operand.clone());
-
-                match operand {
-                    Expression::Constant { value } => match operator {
-                        Operator::Sub => Expression::Constant { value: -value },
-                        Operator::Add => todo!(),
-                        _ => panic!("Cannot apply operator {:?} to an operand in UnaryOp", operator),
-                    },
-                    _ => expression,
-                }
-            }
+    pub fn fold_expression(&self, expression: Expression) -> Expression {
+        if let Some(value) = self.try_fold_constant(&expression) {
+            Self::drop_iteratively(expression);
+            return Expression::Constant { value };
+        }
+
+        match expression {
+            // the parentheses only mattered for parsing, the inner expression can still fold
+            Expression::Grouping { expression } => self.fold_expression(*expression),
             Expression::BinaryOp {
                 left,
-                operator,
+                operator: Operator::Modulo,
                 right,
-            } => {
-                let left = self.evaluate_expression(*left.clone());
-                let right = self.evaluate_expression(*right.clone());
-
-                match (left, right) {
-                    (
-                        Expression::Constant { value: left_value },
-                        Expression::Constant { value: right_value },
-                    ) => match operator {
-                        Operator::Sub => Expression::Constant {
-                            value: left_value - right_value,
-                        },
-                        Operator::Add => Expression::Constant {
-                            value: left_value + right_value,
-                        },
-                        Operator::Multiply => Expression::Constant {
-                           value: left_value * right_value,
-                        },
-                        Operator::Divide => Expression::Constant {
-                            value: left_value / right_value,
-                        }
-                    },
-                    _ => expression,
+            } => self.fold_modulo(self.fold_expression(*left), self.fold_expression(*right)),
+            // unary plus does nothing, even when the operand is only known at runtime
+            Expression::UnaryOp {
+                operator: Operator::Add,
+                operand,
+            } => self.fold_expression(*operand),
+            // a call is never constant, but its arguments still can be
+            Expression::Call { name, args } => Expression::Call {
+                name,
+                args: args
+                    .into_iter()
+                    .map(|arg| self.fold_expression(arg))
+                    .collect(),
+            },
+            _ => expression,
+        }
+    }
+
+    // a remainder by a power of two is left to codegen, which masks it without losing the
+    // sign of a negative dividend
+    fn fold_modulo(&self, left: Expression, right: Expression) -> Expression {
+        match right {
+            Expression::Constant { value: 0 } => panic!(
+                "Division by zero while evaluating {} {} 0",
+                left,
+                Operator::Modulo
+            ),
+            // the dividend still has to be evaluated when it calls a function
+            Expression::Constant { value: 1 } if !left.contains_call() => {
+                Expression::Constant { value: 0 }
+            }
+            _ => Expression::BinaryOp {
+                left: Box::new(left),
+                operator: Operator::Modulo,
+                right: Box::new(right),
+            },
+        }
+    }
+
+    // the default drop of a long chain recurses once per operator, just like evaluating it would
+    fn drop_iteratively(mut expression: Expression) {
+        while let Expression::BinaryOp { left, .. } = expression {
+            expression = *left;
+        }
+    }
+
+    // returns the value of the expression if it can be computed at compile time
+    fn try_fold_constant(&self, expression: &Expression) -> Option<i64> {
+        match expression {
+            Expression::Constant { value } => Some(*value),
+            Expression::UnaryOp { operator, operand } => {
+                let value = self.try_fold_constant(operand)?;
+
+                match operator {
+                    Operator::Sub => Some(self.arithmetic_mode.fold_negation(value)),
+                    Operator::Add => Some(value),
+                    _ => panic!(
+                        "Cannot apply operator {:?} to an operand in UnaryOp",
+                        operator
+                    ),
+                }
+            }
+            Expression::BinaryOp { .. } => {
+                // the parser builds chains like 1 + 1 + ... + 1 leaning to the left, which can
+                // be very deep, so the left operands are walked with a loop instead of recursion
+                let mut right_operands = vec![];
+                let mut leftmost = expression;
+                while let Expression::BinaryOp {
+                    left,
+                    operator,
+                    right,
+                } = leftmost
+                {
+                    right_operands.push((operator, right));
+                    leftmost = left;
+                }
+
+                let mut value = self.try_fold_constant(leftmost)?;
+                for (operator, right) in right_operands.into_iter().rev() {
+                    let right_value = self.try_fold_constant(right)?;
+                    value = self
+                        .arithmetic_mode
+                        .fold_binary(value, operator, right_value);
                 }
+
+                Some(value)
+            }
+            Expression::Grouping { expression } => self.try_fold_constant(expression),
+            Expression::Cast { expression, target } => {
+                let value = self.try_fold_constant(expression)?;
+
+                match target {
+                    Type::I32 => Some(value as i32 as i64),
+                    Type::I64 => Some(value),
+                }
+            }
+            Expression::Call { name, args } if name == LEN_INTRINSIC => match args.as_slice() {
+                // the tokenizer already applied the escape sequences, so this is the byte length
+                [Expression::StringLiteral { value }] => Some(value.len() as i64),
+                // TODO: support strings only known at runtime
+                _ => panic!("{} can only be called with a string literal", LEN_INTRINSIC),
             },
-            Expression::Grouping {expression } => {
-                let new_expression = self.evaluate_expression(*expression.clone());
+            Expression::Call { name, args } if MIN_MAX_INTRINSICS.contains(&name.as_str()) => {
+                if args.is_empty() {
+                    panic!("{} needs at least one argument", name);
+                }
 
-                match new_expression {
-                    Expression::Constant { .. } => new_expression,
-                    _ => *expression.clone(),
+                let values = args
+                    .iter()
+                    .map(|arg| self.try_fold_constant(arg))
+                    .collect::<Option<Vec<i64>>>()?;
+
+                if name == "min" {
+                    values.into_iter().min()
+                } else {
+                    values.into_iter().max()
                 }
             }
-            _ => expression,
+            // booleans stay as they are, codegen materializes them
+            Expression::Call { .. }
+            | Expression::Boolean { .. }
+            | Expression::VariableAccess { .. }
+            | Expression::StringLiteral { .. } => None,
         }
     }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn call(name: &str, args: Vec<Expression>) -> Statement {
+        Statement::Expression(Expression::Call {
+            name: name.to_string(),
+            args,
+        })
+    }
+
     #[test]
     fn do_nothing_with_constant_expression() {
         // given
         let program = Program {
             statements: vec![Statement::Expression(Expression::Constant { value: 5 })],
+            tail: None,
         };
         let evaluator = PartialEvaluator::new(program);
 
@@ -117,6 +232,7 @@ mod tests {
                 operator: Operator::Sub,
                 operand: Box::new(Expression::Constant { value: 5 }),
             })],
+            tail: None,
         };
 
         let evaluator = PartialEvaluator::new(program);
@@ -140,6 +256,7 @@ mod tests {
                 operator: Operator::Sub,
                 right: Box::new(Expression::Constant { value: 3 }),
             })],
+            tail: None,
         };
 
         let evaluator = PartialEvaluator::new(program);
@@ -179,6 +296,7 @@ mod tests {
                     }),
                 }),
             })],
+            tail: None,
         };
 
         let evaluator = PartialEvaluator::new(program);
@@ -205,6 +323,7 @@ mod tests {
                     right: Box::new(Expression::Constant { value: 3 }),
                 },
             }],
+            tail: None,
         };
 
         let evaluator = PartialEvaluator::new(program);
@@ -230,6 +349,7 @@ mod tests {
             statements: vec![Statement::Expression(Expression::BinaryOp {
                 left: Box::new(Expression::VariableAccess {
                     name: "x".to_string(),
+                    location: (0, 0),
                 }),
                 operator: Operator::Sub,
                 right: Box::new(Expression::BinaryOp {
@@ -252,6 +372,7 @@ mod tests {
                     }),
                 }),
             })],
+            tail: None,
         };
         let original_program = program.clone();
 
@@ -263,4 +384,547 @@ mod tests {
         // then
         assert_eq!(result.statements, original_program.statements);
     }
+
+    #[test]
+    fn wrap_on_overflow_in_wrap_mode() {
+        // given
+        let program = i32_max_plus_one_program();
+
+        let evaluator = PartialEvaluator::with_arithmetic_mode(program, ArithmeticMode::Wrap);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant {
+                value: i32::MIN.into()
+            })]
+        );
+    }
+
+    #[test]
+    fn clamp_on_overflow_in_saturate_mode() {
+        // given
+        let program = i32_max_plus_one_program();
+
+        let evaluator = PartialEvaluator::with_arithmetic_mode(program, ArithmeticMode::Saturate);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant {
+                value: i32::MAX.into()
+            })]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Arithmetic overflow while evaluating 2147483647 + 1")]
+    fn report_overflow_in_check_mode() {
+        // given
+        let program = i32_max_plus_one_program();
+
+        let evaluator = PartialEvaluator::with_arithmetic_mode(program, ArithmeticMode::Check);
+
+        // when & then
+        evaluator.evaluate();
+    }
+
+    #[test]
+    fn evaluate_multiply_and_divide_with_constants() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::Expression(Expression::BinaryOp {
+                    left: Box::new(Expression::Constant { value: 6 }),
+                    operator: Operator::Multiply,
+                    right: Box::new(Expression::Constant { value: 7 }),
+                }),
+                Statement::Expression(Expression::BinaryOp {
+                    left: Box::new(Expression::Constant { value: 20 }),
+                    operator: Operator::Divide,
+                    right: Box::new(Expression::Constant { value: 4 }),
+                }),
+            ],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![
+                Statement::Expression(Expression::Constant { value: 42 }),
+                Statement::Expression(Expression::Constant { value: 5 }),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by zero while evaluating 20 / 0")]
+    fn report_division_by_zero() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::Constant { value: 20 }),
+                operator: Operator::Divide,
+                right: Box::new(Expression::Constant { value: 0 }),
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when & then
+        evaluator.evaluate();
+    }
+
+    #[test]
+    fn evaluate_modulo_with_constants() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::Constant { value: -7 }),
+                operator: Operator::Modulo,
+                right: Box::new(Expression::Constant { value: 3 }),
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant { value: -1 })]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by zero while evaluating 7 % 0")]
+    fn report_modulo_by_zero() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::Constant { value: 7 }),
+                operator: Operator::Modulo,
+                right: Box::new(Expression::Constant { value: 0 }),
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::with_arithmetic_mode(program, ArithmeticMode::Saturate);
+
+        // when & then
+        evaluator.evaluate();
+    }
+
+    #[test]
+    fn leave_modulo_by_power_of_two_to_codegen() {
+        // given
+        let program = variable_modulo_program(8);
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::VariableAccess {
+                    name: "x".to_string(),
+                    location: (0, 0),
+                }),
+                operator: Operator::Modulo,
+                right: Box::new(Expression::Constant { value: 8 }),
+            })]
+        );
+    }
+
+    #[test]
+    fn evaluate_modulo_by_one_to_zero() {
+        // given
+        let program = variable_modulo_program(1);
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant { value: 0 })]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by zero while evaluating x % 0")]
+    fn report_modulo_of_variable_by_zero() {
+        // given
+        let program = variable_modulo_program(0);
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when & then
+        evaluator.evaluate();
+    }
+
+    fn variable_modulo_program(divisor: i64) -> Program {
+        Program {
+            statements: vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::VariableAccess {
+                    name: "x".to_string(),
+                    location: (0, 0),
+                }),
+                operator: Operator::Modulo,
+                right: Box::new(Expression::Constant { value: divisor }),
+            })],
+            tail: None,
+        }
+    }
+
+    fn i32_max_plus_one_program() -> Program {
+        Program {
+            statements: vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::Constant {
+                    value: i32::MAX.into(),
+                }),
+                operator: Operator::Add,
+                right: Box::new(Expression::Constant { value: 1 }),
+            })],
+            tail: None,
+        }
+    }
+
+    #[test]
+    fn evaluate_long_addition_chain_without_overflowing_stack() {
+        // given
+        let chain_length = 50_000;
+        let mut expression = Expression::Constant { value: 1 };
+        for _ in 1..chain_length {
+            expression = Expression::BinaryOp {
+                left: Box::new(expression),
+                operator: Operator::Add,
+                right: Box::new(Expression::Constant { value: 1 }),
+            };
+        }
+        let program = Program {
+            statements: vec![Statement::Expression(expression)],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant {
+                value: chain_length
+            })]
+        );
+    }
+
+    #[test]
+    fn truncate_constant_cast_to_i32() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Cast {
+                expression: Box::new(Expression::Constant {
+                    value: (1 << 32) + 1,
+                }),
+                target: Type::I32,
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant { value: 1 })]
+        );
+    }
+
+    #[test]
+    fn fold_single_expression() {
+        // given
+        let evaluator = PartialEvaluator::new(Program::default());
+        let expression = Expression::BinaryOp {
+            left: Box::new(Expression::Constant { value: 3 }),
+            operator: Operator::Add,
+            right: Box::new(Expression::Constant { value: 4 }),
+        };
+
+        // when
+        let result = evaluator.fold_expression(expression);
+
+        // then
+        assert_eq!(Expression::Constant { value: 7 }, result);
+    }
+
+    #[test]
+    fn evaluate_grouped_constants() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::BinaryOp {
+                left: Box::new(Expression::Constant { value: 1 }),
+                operator: Operator::Add,
+                right: Box::new(Expression::Grouping {
+                    expression: Box::new(Expression::BinaryOp {
+                        left: Box::new(Expression::Constant { value: 2 }),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::Constant { value: 3 }),
+                    }),
+                }),
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant { value: 6 })]
+        );
+    }
+
+    #[test]
+    fn evaluate_inside_grouping_with_runtime_value() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Grouping {
+                expression: Box::new(Expression::Grouping {
+                    expression: Box::new(Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        }),
+                        operator: Operator::Modulo,
+                        right: Box::new(Expression::Constant { value: 1 }),
+                    }),
+                }),
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Constant { value: 0 })]
+        );
+    }
+
+    #[test]
+    fn evaluate_call_arguments() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::Grouping {
+                    expression: Box::new(Expression::BinaryOp {
+                        left: Box::new(Expression::Constant { value: 1 }),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::Constant { value: 2 }),
+                    }),
+                }],
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![Statement::Expression(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::Constant { value: 3 }],
+            })]
+        );
+    }
+
+    #[test]
+    fn evaluate_unary_plus_to_operand() {
+        // given
+        let program = Program {
+            statements: vec![
+                Statement::Expression(Expression::UnaryOp {
+                    operator: Operator::Add,
+                    operand: Box::new(Expression::Constant { value: 5 }),
+                }),
+                Statement::Expression(Expression::UnaryOp {
+                    operator: Operator::Add,
+                    operand: Box::new(Expression::VariableAccess {
+                        name: "x".to_string(),
+                        location: (0, 0),
+                    }),
+                }),
+            ],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![
+                Statement::Expression(Expression::Constant { value: 5 }),
+                Statement::Expression(Expression::VariableAccess {
+                    name: "x".to_string(),
+                    location: (0, 0),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluate_len_of_string_literal() {
+        // given
+        let program = Program {
+            statements: ["hello", "\n", "é\\"]
+                .iter()
+                .map(|value| {
+                    call(
+                        "len",
+                        vec![Expression::StringLiteral {
+                            value: value.to_string(),
+                        }],
+                    )
+                })
+                .collect(),
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![
+                Statement::Expression(Expression::Constant { value: 5 }),
+                Statement::Expression(Expression::Constant { value: 1 }),
+                Statement::Expression(Expression::Constant { value: 3 }),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "len can only be called with a string literal")]
+    fn report_len_of_variable() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "len".to_string(),
+                args: vec![Expression::VariableAccess {
+                    name: "x".to_string(),
+                    location: (0, 0),
+                }],
+            })],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when & then
+        evaluator.evaluate();
+    }
+
+    #[test]
+    fn evaluate_max_of_constant_arguments() {
+        // given
+        let partially_constant_args = vec![
+            Expression::VariableAccess {
+                name: "x".to_string(),
+                location: (0, 0),
+            },
+            Expression::BinaryOp {
+                left: Box::new(Expression::Constant { value: 1 }),
+                operator: Operator::Add,
+                right: Box::new(Expression::Constant { value: 2 }),
+            },
+        ];
+        let program = Program {
+            statements: vec![
+                call(
+                    "max",
+                    [3, 9, 5]
+                        .map(|value| Expression::Constant { value })
+                        .to_vec(),
+                ),
+                call(
+                    "min",
+                    [3, 9, -5, 4]
+                        .map(|value| Expression::Constant { value })
+                        .to_vec(),
+                ),
+                call("max", partially_constant_args),
+            ],
+            tail: None,
+        };
+
+        let evaluator = PartialEvaluator::new(program);
+
+        // when
+        let result = evaluator.evaluate();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![
+                Statement::Expression(Expression::Constant { value: 9 }),
+                Statement::Expression(Expression::Constant { value: -5 }),
+                call(
+                    "max",
+                    vec![
+                        Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        },
+                        Expression::Constant { value: 3 },
+                    ]
+                ),
+            ]
+        );
+    }
 }
@@ -0,0 +1,269 @@
+use std::str::FromStr;
+
+use crate::{
+    arithmetic::ArithmeticMode,
+    ast::{Expression, Program, Statement},
+    partial_evaluator::{PartialEvaluator, LEN_INTRINSIC},
+    remove_complex_operands::RemoveComplexOperandsPass,
+};
+
+// the passes between the resolver and codegen, a manifest file can pick which of them run
+// and in what order, with one pass name per line. codegen expects atomic operands, so every
+// manifest ends with remove-complex-operands, and it does not know len, so a program
+// calling len needs partial-evaluate too
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pass {
+    PartialEvaluate,
+    RemoveComplexOperands,
+}
+
+impl Pass {
+    pub const DEFAULT_ORDER: [Pass; 2] = [Self::PartialEvaluate, Self::RemoveComplexOperands];
+
+    pub fn parse_manifest(contents: &str) -> Result<Vec<Self>, String> {
+        let passes: Vec<Self> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+
+        match passes.last() {
+            Some(Self::RemoveComplexOperands) => Ok(passes),
+            _ => Err(
+                "The pass manifest has to end with remove-complex-operands, codegen expects atomic operands"
+                    .to_string(),
+            ),
+        }
+    }
+
+    // only the partial evaluator replaces len, codegen would emit it as a call to an external
+    // function that does not exist
+    pub fn check_lowers_intrinsics(passes: &[Self], program: &Program) -> Result<(), String> {
+        let calls_len = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Expression(expression) => expression,
+                Statement::VariableDeclaration { value, .. } => value,
+            })
+            .chain(&program.tail)
+            .any(|expression| expression.calls(LEN_INTRINSIC));
+
+        if calls_len && !passes.contains(&Self::PartialEvaluate) {
+            return Err(format!(
+                "The program calls {}, so the pass manifest has to include partial-evaluate",
+                LEN_INTRINSIC
+            ));
+        }
+
+        Ok(())
+    }
+
+    // the phase name used in the timings
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PartialEvaluate => "partial_evaluate",
+            Self::RemoveComplexOperands => "remove_complex_operands",
+        }
+    }
+
+    pub fn run(&self, program: GroupedProgram, arithmetic_mode: ArithmeticMode) -> GroupedProgram {
+        let group_sizes: Vec<usize> = program.groups.iter().map(Vec::len).collect();
+
+        // the statements every statement of the flattened program became
+        let (lowered, tail) = match self {
+            Self::PartialEvaluate => {
+                let program =
+                    PartialEvaluator::with_arithmetic_mode(program.into_program(), arithmetic_mode)
+                        .evaluate();
+                let lowered = program.statements.into_iter().map(|s| vec![s]).collect();
+
+                (lowered, program.tail)
+            }
+            Self::RemoveComplexOperands => {
+                RemoveComplexOperandsPass::new(program.into_program()).run_grouped()
+            }
+        };
+
+        let mut lowered = lowered.into_iter();
+        let mut groups: Vec<Vec<Statement>> = group_sizes
+            .iter()
+            .map(|size| lowered.by_ref().take(*size).flatten().collect())
+            .collect();
+        // what is left computes the tail, which always has the last group
+        if let Some(tail_group) = groups.last_mut() {
+            tail_group.extend(lowered.flatten());
+        }
+
+        GroupedProgram { groups, tail }
+    }
+}
+
+// the program with its statements grouped by the source statement they came from, so the
+// listing can show them next to the source. a tail gets the last group, for the statements
+// computing it
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupedProgram {
+    pub groups: Vec<Vec<Statement>>,
+    pub tail: Option<Expression>,
+}
+
+impl GroupedProgram {
+    pub fn into_program(self) -> Program {
+        Program {
+            statements: self.groups.concat(),
+            tail: self.tail,
+        }
+    }
+}
+
+impl From<Program> for GroupedProgram {
+    fn from(program: Program) -> Self {
+        let mut groups: Vec<Vec<Statement>> = program
+            .statements
+            .into_iter()
+            .map(|statement| vec![statement])
+            .collect();
+        if program.tail.is_some() {
+            groups.push(vec![]);
+        }
+
+        Self {
+            groups,
+            tail: program.tail,
+        }
+    }
+}
+
+impl FromStr for Pass {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "partial-evaluate" => Ok(Self::PartialEvaluate),
+            "remove-complex-operands" => Ok(Self::RemoveComplexOperands),
+            _ => Err(format!(
+                "Unknown pass {}, expected one of partial-evaluate, remove-complex-operands",
+                value
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        ast::{Expression, Operator, Statement},
+        parser::Parser,
+    };
+
+    use super::*;
+
+    #[test]
+    fn parse_pass_names_from_manifest() {
+        // given
+        let manifest = "remove-complex-operands\n\n  partial-evaluate  \nremove-complex-operands\n";
+
+        // when
+        let result = Pass::parse_manifest(manifest);
+
+        // then
+        assert_eq!(
+            Ok(vec![
+                Pass::RemoveComplexOperands,
+                Pass::PartialEvaluate,
+                Pass::RemoveComplexOperands
+            ]),
+            result
+        );
+        assert_eq!(
+            Err(
+                "Unknown pass inline, expected one of partial-evaluate, remove-complex-operands"
+                    .to_string()
+            ),
+            Pass::parse_manifest("partial-evaluate\ninline")
+        );
+    }
+
+    #[test]
+    fn reject_manifest_not_ending_with_remove_complex_operands() {
+        // given
+        let manifests = ["", "remove-complex-operands\npartial-evaluate"];
+
+        // when
+        let results: Vec<_> = manifests.iter().map(|m| Pass::parse_manifest(m)).collect();
+
+        // then
+        let error = Err(
+            "The pass manifest has to end with remove-complex-operands, codegen expects atomic operands"
+                .to_string(),
+        );
+        assert_eq!(vec![error.clone(), error], results);
+    }
+
+    #[test]
+    fn require_partial_evaluation_for_len() {
+        // given
+        let program = Parser::parse_str("let x = 1 + len(\"abc\");").unwrap();
+
+        // when
+        let without = Pass::check_lowers_intrinsics(&[Pass::RemoveComplexOperands], &program);
+        let with = Pass::check_lowers_intrinsics(&Pass::DEFAULT_ORDER, &program);
+
+        // then
+        assert_eq!(
+            Err(
+                "The program calls len, so the pass manifest has to include partial-evaluate"
+                    .to_string()
+            ),
+            without
+        );
+        assert_eq!(Ok(()), with);
+    }
+
+    #[test]
+    fn skip_partial_evaluation_left_out_of_manifest() {
+        // given
+        let program = Parser::parse_str("let x = 1 + 2;").unwrap();
+        let passes = Pass::parse_manifest("remove-complex-operands").unwrap();
+
+        // when
+        let result = passes
+            .iter()
+            .fold(GroupedProgram::from(program), |program, pass| {
+                pass.run(program, ArithmeticMode::default())
+            })
+            .into_program();
+
+        // then
+        assert_eq!(
+            vec![Statement::VariableDeclaration {
+                name: "x".to_string(),
+                value: Expression::BinaryOp {
+                    left: Box::new(Expression::Constant { value: 1 }),
+                    operator: Operator::Add,
+                    right: Box::new(Expression::Constant { value: 2 }),
+                },
+            }],
+            result.statements
+        );
+    }
+
+    #[test]
+    fn keep_lowered_statements_with_their_source_statement() {
+        // given
+        let program = Parser::parse_str("let x = f() + 1; x + g()").unwrap();
+
+        // when
+        let result = Pass::RemoveComplexOperands
+            .run(GroupedProgram::from(program), ArithmeticMode::default());
+
+        // then
+        let group_sizes: Vec<usize> = result.groups.iter().map(Vec::len).collect();
+        assert_eq!(vec![2, 1], group_sizes);
+        assert!(result.tail.is_some());
+    }
+}
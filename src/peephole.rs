@@ -0,0 +1,214 @@
+use std::ops::Range;
+
+use crate::codegen::Instruction;
+
+const REGISTERS: [&str; 32] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "eax", "ebx", "ecx", "edx", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d", "r13d", "r14d",
+    "r15d", "ax", "bx", "cx", "dx",
+];
+
+// this pass runs after codegen and cleans up obvious redundancies in the emitted instructions,
+// by looking at every instruction together with the last one that was kept:
+// mov eax, dword [rbp - 4]
+// mov dword [rbp - 4], eax   <- removed, the value is already there
+// mov eax, dword [rbp - 4]
+// mov eax, dword [rbp - 4]   <- removed, same load twice
+// mov eax, 0                 <- becomes xor eax, eax
+// the last rule changes the flags, so codegen must not rely on flags surviving a zeroing mov
+pub struct PeepholeOptimizer {
+    instructions: Vec<Instruction>,
+}
+
+struct Move<'a> {
+    destination: &'a str,
+    source: &'a str,
+}
+
+impl<'a> Move<'a> {
+    fn parse(instruction: &'a str) -> Option<Self> {
+        let (destination, source) = instruction.strip_prefix("mov ")?.split_once(", ")?;
+
+        Some(Self {
+            destination: Self::strip_size(destination),
+            source: Self::strip_size(source),
+        })
+    }
+
+    fn strip_size(operand: &str) -> &str {
+        ["byte ", "word ", "dword ", "qword "]
+            .iter()
+            .find_map(|size| operand.strip_prefix(size))
+            .unwrap_or(operand)
+    }
+}
+
+impl PeepholeOptimizer {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Self { instructions }
+    }
+
+    #[cfg(test)]
+    pub fn run(self) -> Vec<Instruction> {
+        let (optimized, _) = self.run_with_ranges(vec![]);
+
+        optimized
+    }
+
+    // also moves the given ranges of instructions, like the ones codegen returns for every
+    // statement, so they cover the same instructions after some of them were removed
+    pub fn run_with_ranges(
+        self,
+        ranges: Vec<Range<usize>>,
+    ) -> (Vec<Instruction>, Vec<Range<usize>>) {
+        let mut optimized: Vec<Instruction> = vec![];
+        // for every original instruction, where it is or would have been in the optimized ones
+        let mut new_positions = vec![];
+
+        for instruction in self.instructions {
+            new_positions.push(optimized.len());
+
+            let is_redundant = optimized
+                .last()
+                .is_some_and(|previous| Self::is_redundant_after(previous, &instruction));
+
+            if !is_redundant {
+                optimized.push(Self::rewrite_zeroing_move(instruction));
+            }
+        }
+        new_positions.push(optimized.len());
+
+        let ranges = ranges
+            .into_iter()
+            .map(|range| new_positions[range.start]..new_positions[range.end])
+            .collect();
+
+        (optimized, ranges)
+    }
+
+    fn is_redundant_after(previous: &str, current: &str) -> bool {
+        let (Some(previous), Some(current)) = (Move::parse(previous), Move::parse(current)) else {
+            return false;
+        };
+
+        // moving the value back to where it was just copied from
+        let is_move_back =
+            previous.destination == current.source && previous.source == current.destination;
+        // repeating a move, unless it reads the register it overwrites
+        let is_repeated_move = previous.destination == current.destination
+            && previous.source == current.source
+            && !current.source.contains(current.destination);
+
+        is_move_back || is_repeated_move
+    }
+
+    fn rewrite_zeroing_move(instruction: Instruction) -> Instruction {
+        match Move::parse(&instruction) {
+            Some(Move {
+                destination,
+                source: "0",
+            }) if REGISTERS.contains(&destination) => {
+                format!("xor {}, {}", destination, destination)
+            }
+            _ => instruction,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn to_instructions(instructions: &[&str]) -> Vec<Instruction> {
+        instructions.iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn remove_store_of_just_loaded_value() {
+        // given
+        let instructions = to_instructions(&[
+            "mov eax, dword [rbp - 8]",
+            "mov dword [rbp - 8], eax",
+            "call print_int",
+        ]);
+
+        let optimizer = PeepholeOptimizer::new(instructions);
+
+        // when
+        let result = optimizer.run();
+
+        // then
+        assert_eq!(
+            to_instructions(&["mov eax, dword [rbp - 8]", "call print_int"]),
+            result
+        );
+    }
+
+    #[test]
+    fn move_ranges_over_removed_instructions() {
+        // given
+        let instructions = to_instructions(&[
+            "mov dword [rbp - 4], eax",
+            "mov eax, dword [rbp - 4]",
+            "call print_int",
+            "mov eax, 0",
+        ]);
+
+        let optimizer = PeepholeOptimizer::new(instructions);
+
+        // when
+        let (result, ranges) = optimizer.run_with_ranges(vec![0..1, 1..3, 3..4]);
+
+        // then
+        assert_eq!(
+            to_instructions(&["mov dword [rbp - 4], eax", "call print_int", "xor eax, eax"]),
+            result
+        );
+        assert_eq!(vec![0..1, 1..2, 2..3], ranges);
+    }
+
+    #[test]
+    fn replace_zeroing_move_with_xor() {
+        // given
+        let instructions = to_instructions(&["mov eax, 0", "mov dword [rbp - 4], 0"]);
+
+        let optimizer = PeepholeOptimizer::new(instructions);
+
+        // when
+        let result = optimizer.run();
+
+        // then
+        assert_eq!(
+            to_instructions(&["xor eax, eax", "mov dword [rbp - 4], 0"]),
+            result
+        );
+    }
+
+    #[test]
+    fn remove_repeated_load() {
+        // given
+        let instructions = to_instructions(&[
+            "mov eax, dword [rbp - 4]",
+            "mov eax, dword [rbp - 4]",
+            "mov rax, [rax]",
+            "mov rax, [rax]",
+        ]);
+
+        let optimizer = PeepholeOptimizer::new(instructions);
+
+        // when
+        let result = optimizer.run();
+
+        // then
+        assert_eq!(
+            to_instructions(&[
+                "mov eax, dword [rbp - 4]",
+                "mov rax, [rax]",
+                "mov rax, [rax]"
+            ]),
+            result
+        );
+    }
+}
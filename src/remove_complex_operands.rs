@@ -1,8 +1,14 @@
+use std::collections::HashMap;
+
 use crate::ast::{Expression, Program, Statement};
 
 pub struct RemoveComplexOperandsPass {
     program: Program,
     temp_variable_index: u16,
+    // temporaries holding expressions without calls, keyed by their source form so that
+    // spans do not matter, reused for the same expression within one statement
+    // it is only ever looked up, never iterated, so hash randomization cannot change the output
+    pure_temporaries: HashMap<String, String>,
 }
 
 struct TransformExpressionResult {
@@ -31,22 +37,46 @@ impl RemoveComplexOperandsPass {
         Self {
             program,
             temp_variable_index: 0,
+            pure_temporaries: HashMap::new(),
         }
     }
 
+    #[cfg(test)]
     pub fn run(mut self) -> Program {
+        let (groups, tail) = self.run_grouped();
+
         Program {
-            statements: self
-                .program
-                .statements
-                .clone() // TODO: How to get rid of this clone?
-                .iter()
-                .flat_map(|statement| self.transform_statement(statement.clone()))
-                .collect(),
+            statements: groups.concat(),
+            tail,
         }
     }
 
+    // same as run, but keeps the statements produced for every original statement together,
+    // the statements computing the tail come last in a group of their own
+    pub fn run_grouped(&mut self) -> (Vec<Vec<Statement>>, Option<Expression>) {
+        let mut groups: Vec<Vec<Statement>> = self
+            .program
+            .statements
+            .clone() // TODO: How to get rid of this clone?
+            .into_iter()
+            .map(|statement| self.transform_statement(statement))
+            .collect();
+
+        let tail = self.program.tail.take().map(|tail| {
+            self.pure_temporaries.clear();
+            let result = self.transform_expression(tail, false);
+            groups.push(result.additional_statements);
+
+            result.expression
+        });
+
+        (groups, tail)
+    }
+
     fn transform_statement(&mut self, statement: Statement) -> Vec<Statement> {
+        // a later statement can declare a variable with the same name as an operand
+        self.pure_temporaries.clear();
+
         match statement {
             Statement::Expression(expression) => {
                 let result = self.transform_expression(expression, false);
@@ -85,6 +115,8 @@ impl RemoveComplexOperandsPass {
         match expression {
             Expression::Constant { .. } => expression.into(),
             Expression::VariableAccess { .. } => expression.into(),
+            Expression::StringLiteral { .. } => expression.into(),
+            Expression::Boolean { .. } => expression.into(),
             Expression::UnaryOp { operator, operand } => {
                 let operand = self.transform_expression(*operand, true);
                 let mut additional_statements = operand.additional_statements;
@@ -99,17 +131,18 @@ impl RemoveComplexOperandsPass {
                     };
                 }
 
-                let (temp_variable_name, temp_variable_statement) = self
-                    .declare_temporary_variable(Expression::UnaryOp {
+                let temp_variable_name = self.reuse_or_declare_temporary_variable(
+                    Expression::UnaryOp {
                         operator,
                         operand: Box::new(operand.expression),
-                    });
-
-                additional_statements.push(temp_variable_statement);
+                    },
+                    &mut additional_statements,
+                );
 
                 TransformExpressionResult {
                     expression: Expression::VariableAccess {
                         name: temp_variable_name,
+                        location: (0, 0),
                     },
                     additional_statements,
                 }
@@ -130,7 +163,7 @@ impl RemoveComplexOperandsPass {
                 let mut additional_statements: Vec<Statement> = left
                     .additional_statements
                     .into_iter()
-                    .chain(right.additional_statements.into_iter())
+                    .chain(right.additional_statements)
                     .collect();
 
                 if !should_create_temporary_variable {
@@ -140,13 +173,15 @@ impl RemoveComplexOperandsPass {
                     };
                 }
 
-                let (temp_variable_name, temp_variable_statement) =
-                    self.declare_temporary_variable(new_expression);
-                additional_statements.push(temp_variable_statement);
+                let temp_variable_name = self.reuse_or_declare_temporary_variable(
+                    new_expression,
+                    &mut additional_statements,
+                );
 
                 TransformExpressionResult {
                     expression: Expression::VariableAccess {
                         name: temp_variable_name,
+                        location: (0, 0),
                     },
                     additional_statements,
                 }
@@ -174,6 +209,7 @@ impl RemoveComplexOperandsPass {
                         });
                     let new_expression = Expression::VariableAccess {
                         name: temp_variable_name,
+                        location: (0, 0),
                     };
 
                     additional_statements.push(temp_variable_statement);
@@ -187,23 +223,64 @@ impl RemoveComplexOperandsPass {
                 TransformExpressionResult {
                     expression: Expression::Call {
                         name,
-                        args: new_args
+                        args: new_args,
                     },
                     additional_statements,
                 }
             }
-            Expression::Grouping { expression } => {
-                dbg!(&expression);
+            Expression::Cast { expression, target } => {
                 let expression = self.transform_expression(*expression, true);
+                let mut additional_statements = expression.additional_statements;
+                let new_expression = Expression::Cast {
+                    expression: Box::new(expression.expression),
+                    target,
+                };
+
+                if !should_create_temporary_variable {
+                    return TransformExpressionResult {
+                        expression: new_expression,
+                        additional_statements,
+                    };
+                }
+
+                let temp_variable_name = self.reuse_or_declare_temporary_variable(
+                    new_expression,
+                    &mut additional_statements,
+                );
 
                 TransformExpressionResult {
-                    expression: Expression::Grouping {
-                        expression: Box::new(expression.expression)
+                    expression: Expression::VariableAccess {
+                        name: temp_variable_name,
+                        location: (0, 0),
                     },
-                    additional_statements: expression.additional_statements
+                    additional_statements,
                 }
             }
+            // the parentheses only matter for parsing, codegen works on the inner expression
+            Expression::Grouping { expression } => {
+                self.transform_expression(*expression, should_create_temporary_variable)
+            }
+        }
+    }
+
+    // operands are atomic by now, so only calls can have side effects and those are never reused
+    fn reuse_or_declare_temporary_variable(
+        &mut self,
+        pure_expression: Expression,
+        additional_statements: &mut Vec<Statement>,
+    ) -> String {
+        let key = pure_expression.to_string();
+        if let Some(temp_variable_name) = self.pure_temporaries.get(&key) {
+            return temp_variable_name.clone();
         }
+
+        let (temp_variable_name, temp_variable_statement) =
+            self.declare_temporary_variable(pure_expression);
+        additional_statements.push(temp_variable_statement);
+        self.pure_temporaries
+            .insert(key, temp_variable_name.clone());
+
+        temp_variable_name
     }
 
     fn declare_temporary_variable(
@@ -229,6 +306,13 @@ mod test {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    fn variable(name: &str) -> Expression {
+        Expression::VariableAccess {
+            name: name.to_string(),
+            location: (0, 0),
+        }
+    }
+
     #[test]
     fn do_not_transform_method_call_with_variable_access() {
         // given
@@ -242,9 +326,11 @@ mod test {
                     name: "print_int".to_string(),
                     args: vec![Expression::VariableAccess {
                         name: "test".to_string(),
+                        location: (0, 0),
                     }],
                 }),
             ],
+            tail: None,
         };
         let original_program = program.clone();
 
@@ -282,6 +368,7 @@ mod test {
                     right: Box::new(Expression::Constant { value: 3 }),
                 },
             }],
+            tail: None,
         };
 
         let pass = RemoveComplexOperandsPass::new(program);
@@ -312,7 +399,8 @@ mod test {
                     value: Expression::Call {
                         name: "get_number_2".to_string(),
                         args: vec![Expression::VariableAccess {
-                            name: "tmp_1".to_string()
+                            name: "tmp_1".to_string(),
+                            location: (0, 0),
                         }],
                     },
                 },
@@ -320,11 +408,13 @@ mod test {
                     name: "tmp_3".to_string(),
                     value: Expression::BinaryOp {
                         left: Box::new(Expression::VariableAccess {
-                            name: "tmp_0".to_string()
+                            name: "tmp_0".to_string(),
+                            location: (0, 0),
                         }),
                         operator: Operator::Add,
                         right: Box::new(Expression::VariableAccess {
-                            name: "tmp_2".to_string()
+                            name: "tmp_2".to_string(),
+                            location: (0, 0),
                         })
                     },
                 },
@@ -332,7 +422,8 @@ mod test {
                     name: "test".to_string(),
                     value: Expression::BinaryOp {
                         left: Box::new(Expression::VariableAccess {
-                            name: "tmp_3".to_string()
+                            name: "tmp_3".to_string(),
+                            location: (0, 0),
                         }),
                         operator: Operator::Sub,
                         right: Box::new(Expression::Constant { value: 3 }),
@@ -342,6 +433,58 @@ mod test {
         )
     }
 
+    #[test]
+    fn keep_call_statement_after_hoisting_its_arguments() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::BinaryOp {
+                    left: Box::new(Expression::BinaryOp {
+                        left: Box::new(variable("a")),
+                        operator: Operator::Multiply,
+                        right: Box::new(Expression::Constant { value: 2 }),
+                    }),
+                    operator: Operator::Add,
+                    right: Box::new(Expression::Constant { value: 1 }),
+                }],
+            })],
+            tail: None,
+        };
+
+        let pass = RemoveComplexOperandsPass::new(program);
+
+        // when
+        let result = pass.run();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![
+                Statement::VariableDeclaration {
+                    name: "tmp_0".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(variable("a")),
+                        operator: Operator::Multiply,
+                        right: Box::new(Expression::Constant { value: 2 }),
+                    },
+                },
+                Statement::VariableDeclaration {
+                    name: "tmp_1".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(variable("tmp_0")),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::Constant { value: 1 }),
+                    },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![variable("tmp_1")],
+                }),
+            ]
+        )
+    }
+
     #[test]
     fn transform_variable_declaration_with_nested_expressions() {
         // given
@@ -367,6 +510,7 @@ mod test {
                     }),
                 },
             }],
+            tail: None,
         };
 
         let pass = RemoveComplexOperandsPass::new(program);
@@ -391,7 +535,8 @@ mod test {
                         left: Box::new(Expression::Constant { value: 3 }),
                         operator: Operator::Add,
                         right: Box::new(Expression::VariableAccess {
-                            name: "tmp_0".to_string()
+                            name: "tmp_0".to_string(),
+                            location: (0, 0),
                         })
                     },
                 },
@@ -407,7 +552,8 @@ mod test {
                     value: Expression::UnaryOp {
                         operator: Operator::Sub,
                         operand: Box::new(Expression::VariableAccess {
-                            name: "tmp_2".to_string()
+                            name: "tmp_2".to_string(),
+                            location: (0, 0),
                         }),
                     },
                 },
@@ -415,15 +561,271 @@ mod test {
                     name: "test".to_string(),
                     value: Expression::BinaryOp {
                         left: Box::new(Expression::VariableAccess {
-                            name: "tmp_1".to_string()
+                            name: "tmp_1".to_string(),
+                            location: (0, 0),
                         }),
                         operator: Operator::Sub,
                         right: Box::new(Expression::VariableAccess {
-                            name: "tmp_3".to_string()
+                            name: "tmp_3".to_string(),
+                            location: (0, 0),
                         }),
                     },
                 }
             ]
         )
     }
+
+    #[test]
+    fn hoist_call_out_of_grouped_initializer() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "y".to_string(),
+                value: Expression::Grouping {
+                    expression: Box::new(Expression::BinaryOp {
+                        left: Box::new(Expression::Call {
+                            name: "get_x".to_string(),
+                            args: vec![],
+                        }),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::Constant { value: 1 }),
+                    }),
+                },
+            }],
+            tail: None,
+        };
+
+        let pass = RemoveComplexOperandsPass::new(program);
+
+        // when
+        let result = pass.run();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![
+                Statement::VariableDeclaration {
+                    name: "tmp_0".to_string(),
+                    value: Expression::Call {
+                        name: "get_x".to_string(),
+                        args: vec![],
+                    },
+                },
+                Statement::VariableDeclaration {
+                    name: "y".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "tmp_0".to_string(),
+                            location: (0, 0),
+                        }),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::Constant { value: 1 }),
+                    },
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn unwrap_grouped_call_argument() {
+        // given
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::Call {
+                name: "print_int".to_string(),
+                args: vec![Expression::Grouping {
+                    expression: Box::new(Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        }),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::Constant { value: 2 }),
+                    }),
+                }],
+            })],
+            tail: None,
+        };
+
+        let pass = RemoveComplexOperandsPass::new(program);
+
+        // when
+        let result = pass.run();
+
+        // then
+        assert_eq!(
+            result.statements,
+            vec![
+                Statement::VariableDeclaration {
+                    name: "tmp_0".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "x".to_string(),
+                            location: (0, 0),
+                        }),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::Constant { value: 2 }),
+                    },
+                },
+                Statement::Expression(Expression::Call {
+                    name: "print_int".to_string(),
+                    args: vec![Expression::VariableAccess {
+                        name: "tmp_0".to_string(),
+                        location: (0, 0),
+                    }],
+                }),
+            ]
+        )
+    }
+
+    #[test]
+    fn compute_repeated_pure_expression_once() {
+        // given
+        let a_plus_b = |column| Expression::Grouping {
+            expression: Box::new(Expression::BinaryOp {
+                left: Box::new(Expression::VariableAccess {
+                    name: "a".to_string(),
+                    location: (1, column),
+                }),
+                operator: Operator::Add,
+                right: Box::new(Expression::VariableAccess {
+                    name: "b".to_string(),
+                    location: (1, column + 4),
+                }),
+            }),
+        };
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "c".to_string(),
+                value: Expression::BinaryOp {
+                    left: Box::new(a_plus_b(10)),
+                    operator: Operator::Multiply,
+                    right: Box::new(a_plus_b(20)),
+                },
+            }],
+            tail: None,
+        };
+
+        let pass = RemoveComplexOperandsPass::new(program);
+
+        // when
+        let result = pass.run();
+
+        // then
+        let tmp_0 = || {
+            Box::new(Expression::VariableAccess {
+                name: "tmp_0".to_string(),
+                location: (0, 0),
+            })
+        };
+        assert_eq!(
+            vec![
+                Statement::VariableDeclaration {
+                    name: "tmp_0".to_string(),
+                    value: Expression::BinaryOp {
+                        left: Box::new(Expression::VariableAccess {
+                            name: "a".to_string(),
+                            location: (1, 10),
+                        }),
+                        operator: Operator::Add,
+                        right: Box::new(Expression::VariableAccess {
+                            name: "b".to_string(),
+                            location: (1, 14),
+                        }),
+                    },
+                },
+                Statement::VariableDeclaration {
+                    name: "c".to_string(),
+                    value: Expression::BinaryOp {
+                        left: tmp_0(),
+                        operator: Operator::Multiply,
+                        right: tmp_0(),
+                    },
+                },
+            ],
+            result.statements
+        );
+    }
+
+    #[test]
+    fn keep_repeated_calls() {
+        // given
+        let f_of_x = || {
+            Box::new(Expression::Call {
+                name: "f".to_string(),
+                args: vec![Expression::VariableAccess {
+                    name: "x".to_string(),
+                    location: (0, 0),
+                }],
+            })
+        };
+        let program = Program {
+            statements: vec![Statement::Expression(Expression::BinaryOp {
+                left: f_of_x(),
+                operator: Operator::Add,
+                right: f_of_x(),
+            })],
+            tail: None,
+        };
+
+        let pass = RemoveComplexOperandsPass::new(program);
+
+        // when
+        let result = pass.run();
+
+        // then
+        assert_eq!(
+            vec![
+                Statement::VariableDeclaration {
+                    name: "tmp_0".to_string(),
+                    value: *f_of_x(),
+                },
+                Statement::VariableDeclaration {
+                    name: "tmp_1".to_string(),
+                    value: *f_of_x(),
+                },
+                Statement::Expression(Expression::BinaryOp {
+                    left: Box::new(variable("tmp_0")),
+                    operator: Operator::Add,
+                    right: Box::new(variable("tmp_1")),
+                }),
+            ],
+            result.statements
+        );
+    }
+
+    #[test]
+    fn produce_identical_statements_for_identical_programs() {
+        // given
+        let sum = |left: &str, right: &str| Expression::BinaryOp {
+            left: Box::new(variable(left)),
+            operator: Operator::Add,
+            right: Box::new(variable(right)),
+        };
+        let program = Program {
+            statements: (0..20)
+                .map(|index| {
+                    Statement::Expression(Expression::Call {
+                        name: "f".to_string(),
+                        args: vec![
+                            sum("a", "b"),
+                            sum(&format!("c{}", index), "d"),
+                            sum("a", "b"),
+                            Expression::Call {
+                                name: "g".to_string(),
+                                args: vec![sum("a", "b")],
+                            },
+                        ],
+                    })
+                })
+                .collect(),
+            tail: Some(sum("a", "b")),
+        };
+
+        // when
+        let first = RemoveComplexOperandsPass::new(program.clone()).run();
+        let second = RemoveComplexOperandsPass::new(program).run();
+
+        // then
+        assert_eq!(first, second);
+    }
 }
@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::{Expression, Program, Statement},
+    tokenizer::Keyword,
+};
+
+// this compiler pass makes sure that every variable access refers to a variable
+// that has been declared before it, so that mistakes like:
+// let b = a + 1;
+// are reported with the location of `a` instead of failing somewhere in codegen
+pub struct Resolver {
+    program: Program,
+    declared_variables: HashSet<String>,
+}
+
+impl Resolver {
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            declared_variables: HashSet::new(),
+        }
+    }
+
+    pub fn resolve(mut self) -> Program {
+        let statements = std::mem::take(&mut self.program.statements);
+
+        for statement in &statements {
+            self.resolve_statement(statement);
+        }
+
+        if let Some(tail) = &self.program.tail {
+            self.resolve_expression(tail);
+        }
+
+        Program {
+            statements,
+            tail: self.program.tail,
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(expression) => self.resolve_expression(expression),
+            Statement::VariableDeclaration { name, value } => {
+                // the initializer is resolved first, so `let a = a;` is still an error
+                self.resolve_expression(value);
+                if Keyword::all().any(|word| word == name) {
+                    panic!("Cannot use reserved word {} as a variable name", name);
+                }
+                self.declared_variables.insert(name.clone());
+            }
+        }
+    }
+
+    fn resolve_expression(&self, expression: &Expression) {
+        match expression {
+            Expression::Constant { .. }
+            | Expression::StringLiteral { .. }
+            | Expression::Boolean { .. } => {}
+            Expression::UnaryOp { operand, .. } => self.resolve_expression(operand),
+            Expression::BinaryOp { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expression::Call { args, .. } => {
+                for arg in args {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expression::VariableAccess { name, location } => {
+                if !self.declared_variables.contains(name) {
+                    panic!(
+                        "Use of undeclared variable {} at {}:{}",
+                        name, location.0, location.1
+                    );
+                }
+            }
+            Expression::Grouping { expression } => self.resolve_expression(expression),
+            Expression::Cast { expression, .. } => self.resolve_expression(expression),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parser::Parser, tokenizer::Tokenizer};
+
+    use super::*;
+
+    #[test]
+    fn accept_access_to_declared_variable() {
+        // given
+        let source = r#"let a = 1;
+let b = a + 1;
+print_int(b);"#;
+        let program = Parser::new(Tokenizer::new(source).tokenize()).parse();
+        let original_program = program.clone();
+
+        let resolver = Resolver::new(program);
+
+        // when
+        let result = resolver.resolve();
+
+        // then
+        assert_eq!(original_program, result);
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of undeclared variable a at 1:8")]
+    fn report_use_of_undeclared_variable() {
+        // given
+        let source = r#"let b = a + 1;"#;
+        let program = Parser::new(Tokenizer::new(source).tokenize()).parse();
+
+        let resolver = Resolver::new(program);
+
+        // when & then
+        resolver.resolve();
+    }
+
+    #[test]
+    #[should_panic(expected = "Use of undeclared variable a")]
+    fn report_variable_used_in_its_own_initializer() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "a".to_string(),
+                value: Expression::VariableAccess {
+                    name: "a".to_string(),
+                    location: (1, 8),
+                },
+            }],
+            tail: None,
+        };
+
+        let resolver = Resolver::new(program);
+
+        // when & then
+        resolver.resolve();
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot use reserved word const as a variable name")]
+    fn report_variable_named_like_keyword() {
+        // given
+        let program = Program {
+            statements: vec![Statement::VariableDeclaration {
+                name: "const".to_string(),
+                value: Expression::Constant { value: 1 },
+            }],
+            tail: None,
+        };
+
+        let resolver = Resolver::new(program);
+
+        // when & then
+        resolver.resolve();
+    }
+}
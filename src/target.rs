@@ -0,0 +1,124 @@
+// the platform the compiled program runs on, the generated code, the object format
+// and the runtime all have to agree on it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Linux,
+    MacOs,
+}
+
+impl Target {
+    pub fn host() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Linux
+        }
+    }
+
+    pub fn object_format(&self) -> &'static str {
+        match self {
+            Self::Linux => "elf64",
+            Self::MacOs => "macho64",
+        }
+    }
+
+    // Mach-O symbols start with an underscore, nasm adds it to every global and extern
+    // symbol, so the generated code keeps using the plain names
+    pub fn assembler_args(&self) -> Vec<&'static str> {
+        let mut args = vec!["-f", self.object_format()];
+
+        if let Self::MacOs = self {
+            args.extend(["--prefix", "_"]);
+        }
+
+        args
+    }
+
+    // the program is linked for x86-64 like the runtime, macOS has no static libc to link
+    pub fn link_args(&self, link_static: bool) -> Result<Vec<&'static str>, String> {
+        match (self, link_static) {
+            (Self::Linux, false) => Ok(vec![]),
+            (Self::Linux, true) => Ok(vec!["-static"]),
+            (Self::MacOs, false) => Ok(vec!["-arch", "x86_64"]),
+            (Self::MacOs, true) => Err("Static linking is not supported on macOS".to_string()),
+        }
+    }
+
+    pub fn runtime_compile_args(&self) -> Vec<&'static str> {
+        let mut args = vec!["-c", "runtime.c", "-o", "runtime.o"];
+
+        // the generated assembly is x86-64 only, so the runtime has to match it on arm macs
+        if let Self::MacOs = self {
+            args.extend(["-arch", "x86_64"]);
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn compile_runtime_for_x86_64_on_macos() {
+        // given
+        let target = Target::MacOs;
+
+        // when
+        let result = target.runtime_compile_args();
+
+        // then
+        assert_eq!(
+            vec!["-c", "runtime.c", "-o", "runtime.o", "-arch", "x86_64"],
+            result
+        );
+    }
+
+    #[test]
+    fn compile_runtime_without_extra_flags_on_linux() {
+        // given
+        let target = Target::Linux;
+
+        // when
+        let result = target.runtime_compile_args();
+
+        // then
+        assert_eq!(vec!["-c", "runtime.c", "-o", "runtime.o"], result);
+    }
+
+    #[test]
+    fn prefix_symbols_with_underscore_on_macos() {
+        // given
+        let targets = [Target::Linux, Target::MacOs];
+
+        // when
+        let result: Vec<Vec<&str>> = targets.iter().map(Target::assembler_args).collect();
+
+        // then
+        assert_eq!(
+            vec![vec!["-f", "elf64"], vec!["-f", "macho64", "--prefix", "_"]],
+            result
+        );
+    }
+
+    #[test]
+    fn link_for_x86_64_without_static_libc_on_macos() {
+        // given
+        let target = Target::MacOs;
+
+        // when
+        let dynamic = target.link_args(false);
+        let r#static = target.link_args(true);
+
+        // then
+        assert_eq!(Ok(vec!["-arch", "x86_64"]), dynamic);
+        assert_eq!(
+            Err("Static linking is not supported on macOS".to_string()),
+            r#static
+        );
+        assert_eq!(Ok(vec!["-static"]), Target::Linux.link_args(true));
+    }
+}
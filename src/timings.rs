@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+// wall clock duration of every compiler phase, dumped as json so benchmarking
+// scripts can track the compiler performance over time
+#[derive(Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+    pub token_count: usize,
+    pub statement_count: usize,
+    pub instruction_count: usize,
+}
+
+impl Timings {
+    pub fn measure<T>(&mut self, phase: &'static str, run: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = run();
+        self.phases.push((phase, start.elapsed()));
+
+        result
+    }
+
+    pub fn to_json(&self) -> String {
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(name, duration)| {
+                format!(
+                    "{{\"name\": \"{}\", \"duration_us\": {}}}",
+                    name,
+                    duration.as_micros()
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"phases\": [{}], \"token_count\": {}, \"statement_count\": {}, \"instruction_count\": {}}}",
+            phases.join(", "),
+            self.token_count,
+            self.statement_count,
+            self.instruction_count
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn dump_duration_of_every_phase_as_json() {
+        // given
+        let mut timings = Timings::default();
+        let tokens = timings.measure("tokenize", || vec![1, 2, 3]);
+        timings.measure("parse", || ());
+        timings.token_count = tokens.len();
+
+        // when
+        let result = timings.to_json();
+
+        // then
+        let phases: Vec<&str> = result
+            .split("\"name\": ")
+            .skip(1)
+            .map(|phase| phase.split(',').next().unwrap())
+            .collect();
+        assert!(result.starts_with("{\"phases\": [{"));
+        assert_eq!(vec!["\"tokenize\"", "\"parse\""], phases);
+        assert_eq!(2, result.matches("\"duration_us\": ").count());
+        assert!(result
+            .ends_with("], \"token_count\": 3, \"statement_count\": 0, \"instruction_count\": 0}"));
+    }
+}
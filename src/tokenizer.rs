@@ -4,11 +4,31 @@ use std::fmt;
 pub struct Token {
     r#type: TokenType,
     location: (usize, usize),
+    // byte offset of the token in the source
+    offset: usize,
     length: usize,
     literal_value: String,
 }
 
 impl Token {
+    // lets parser tests build token streams without going through the tokenizer,
+    // a hand made token has no source text, so its offset is 0
+    #[cfg(test)]
+    pub fn new(
+        r#type: TokenType,
+        literal_value: &str,
+        location: (usize, usize),
+        length: usize,
+    ) -> Self {
+        Self {
+            r#type,
+            location,
+            offset: 0,
+            length,
+            literal_value: literal_value.to_string(),
+        }
+    }
+
     pub fn get_type(&self) -> &TokenType {
         &self.r#type
     }
@@ -16,6 +36,18 @@ impl Token {
     pub fn get_literal_value(&self) -> &str {
         &self.literal_value
     }
+
+    pub fn get_location(&self) -> (usize, usize) {
+        self.location
+    }
+
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn get_length(&self) -> usize {
+        self.length
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -31,6 +63,7 @@ pub enum TokenType {
     Minus,
     Star,
     Slash,
+    Percent,
     Semicolon,
     Not,
 
@@ -49,7 +82,9 @@ pub enum TokenType {
     // multi character tokens
     Identifier,
     Number,
+    Float,
     String,
+    Char,
     Keyword(Keyword),
 
     Eof,
@@ -65,46 +100,86 @@ impl fmt::Display for TokenType {
 pub enum Keyword {
     Let,
     Const,
+    As,
+    Print,
+    True,
+    False,
 }
 
 impl Keyword {
-    pub fn try_match_from_raw_value(raw: &str) -> Option<Keyword> {
-        match raw {
-            "let" => Some(Keyword::Let),
-            "const" => Some(Keyword::Const),
-            _ => None,
+    // a new keyword only has to be added here and to `as_str`
+    pub const ALL: [Keyword; 6] = [
+        Self::Let,
+        Self::Const,
+        Self::As,
+        Self::Print,
+        Self::True,
+        Self::False,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Let => "let",
+            Self::Const => "const",
+            Self::As => "as",
+            Self::Print => "print",
+            Self::True => "true",
+            Self::False => "false",
         }
     }
+
+    // every reserved word, these can never be used as identifiers
+    pub fn all() -> impl Iterator<Item = &'static str> {
+        Self::ALL.iter().map(Self::as_str)
+    }
+
+    pub fn try_match_from_raw_value(raw: &str) -> Option<Keyword> {
+        Self::ALL
+            .into_iter()
+            .find(|keyword| keyword.as_str() == raw)
+    }
 }
 
 pub struct Tokenizer {
     source: String,
     cursor: usize,
     current_line: usize,
+    // cursor and offsets are in bytes, so tokens can slice the source, while
+    // columns count characters from the start of the line, starting at 0
     current_column: usize,
     current_token_start: usize,
+    current_token_column: usize,
 }
 
 impl Tokenizer {
-    pub fn new(source: String) -> Self {
+    pub fn new(source: impl Into<String>) -> Self {
         Self {
-            source,
+            source: source.into(),
             cursor: 0,
             current_line: 1,
             current_column: 0,
             current_token_start: 0,
+            current_token_column: 0,
         }
     }
 
     pub fn tokenize(&mut self) -> Vec<Token> {
         let mut tokens = vec![];
 
+        // a shebang line makes the source an executable script, it is not part of the program
+        if self.source.starts_with("#!") {
+            self.cursor = self.source.find('\n').unwrap_or(self.source.len());
+        }
+
         while !self.is_at_end() {
-            self.current_token_start = self.cursor;
+            self.mark_token_start();
             if let Some(token) = self.consume_token() {
                 tokens.push(token);
             }
         }
+
+        // Eof takes no characters, it marks the position right after the source
+        self.mark_token_start();
         tokens.push(self.make_token(TokenType::Eof));
 
         tokens
@@ -112,18 +187,16 @@ impl Tokenizer {
 
     fn consume_token(&mut self) -> Option<Token> {
         let mut c = self.consume_char();
-        while c.is_whitespace() || c == '\n' || c == '\r' {
+        while c.is_whitespace() {
+            self.count_line_break(c);
+
+            // a source ending with whitespace has no more tokens, only Eof follows
             if self.is_at_end() {
                 return None;
             }
 
-            if c == '\n' {
-                self.current_line += 1;
-                self.current_column = 1;
-            }
-
+            self.mark_token_start();
             c = self.consume_char();
-            self.current_token_start = self.cursor - 1;
         }
 
         let token = match c {
@@ -135,7 +208,20 @@ impl Tokenizer {
             '.' => self.make_token(TokenType::Dot),
             '+' => self.make_token(TokenType::Plus),
             '*' => self.make_token(TokenType::Star),
-            '/' => self.make_token(TokenType::Slash),
+            '%' => self.make_token(TokenType::Percent),
+            '/' => {
+                if self.peek_char(0) == Some('*') {
+                    self.skip_block_comment();
+                    return None;
+                }
+
+                if self.peek_char(0) == Some('/') {
+                    self.skip_line_comment();
+                    return None;
+                }
+
+                self.make_token(TokenType::Slash)
+            }
             ';' => self.make_token(TokenType::Semicolon),
             '-' => {
                 if self.match_next_char('>') {
@@ -180,17 +266,18 @@ impl Tokenizer {
                 } else {
                     panic!(
                         "Unexpected character {} at {}:{}",
-                        c, self.current_line, self.current_column
+                        c, self.current_line, self.current_token_column
                     );
                 }
             }
             '"' => self.consume_string(),
+            '\'' => self.consume_char_literal(),
             '0'..='9' => self.consume_number(),
             'a'..='z' | 'A'..='Z' | '_' => self.consume_identifier_or_keyword(),
             _ => {
                 panic!(
                     "Unexpected character {} at {}:{}",
-                    c, self.current_line, self.current_column
+                    c, self.current_line, self.current_token_column
                 );
             }
         };
@@ -198,75 +285,300 @@ impl Tokenizer {
         Some(token)
     }
 
+    // the byte offset and the column of a token are where its first character is
+    fn mark_token_start(&mut self) {
+        self.current_token_start = self.cursor;
+        self.current_token_column = self.current_column;
+    }
+
     fn consume_char(&mut self) -> char {
-        let c = self.source.as_bytes()[self.cursor] as char;
+        let c = self.peek_next_char();
 
-        self.cursor += 1;
+        self.cursor += c.len_utf8();
         self.current_column += 1;
 
         c
     }
 
+    // \r\n is a single line break, a lone \r is only whitespace,
+    // returns whether c started a line break
+    fn count_line_break(&mut self, c: char) -> bool {
+        let is_line_break = match c {
+            '\n' => true,
+            '\r' if self.peek_char(0) == Some('\n') => {
+                self.consume_char();
+                true
+            }
+            _ => false,
+        };
+
+        if is_line_break {
+            self.current_line += 1;
+            self.current_column = 0;
+        }
+
+        is_line_break
+    }
+
+    // the line break is left for the whitespace loop, which counts the line
+    fn skip_line_comment(&mut self) {
+        while self.peek_char(0).is_some_and(|c| c != '\n') {
+            self.consume_char();
+        }
+    }
+
+    // block comments nest, so /* a /* b */ c */ is a single comment
+    fn skip_block_comment(&mut self) {
+        let start = (self.current_line, self.current_token_column);
+        self.consume_char();
+
+        let mut depth = 1;
+        while !self.is_at_end() {
+            let c = self.consume_char();
+
+            if self.count_line_break(c) {
+                continue;
+            } else if c == '/' && self.peek_char(0) == Some('*') {
+                self.consume_char();
+                depth += 1;
+            } else if c == '*' && self.peek_char(0) == Some('/') {
+                self.consume_char();
+                depth -= 1;
+
+                if depth == 0 {
+                    return;
+                }
+            }
+        }
+
+        panic!(
+            "Unterminated block comment, begins at {}:{}",
+            start.0, start.1
+        );
+    }
+
+    // the literal value holds the string with its escape sequences already applied
     fn consume_string(&mut self) -> Token {
         let mut is_terminated = false;
+        let mut value = String::new();
 
         while !self.is_at_end() {
             let c = self.consume_char();
-            if c == '"' {
-                is_terminated = true;
-                break;
-            };
+            match c {
+                '"' => {
+                    is_terminated = true;
+                    break;
+                }
+                '\\' if !self.is_at_end() => value.push(self.consume_escape_sequence()),
+                _ => value.push(c),
+            }
         }
 
         if !is_terminated {
             panic!(
                 "Unterminated string encountered, begins at {}:{}",
-                self.current_line, self.current_token_start
+                self.current_line, self.current_token_column
             );
         }
 
         // start  + 1, because token start points at the opening quote
         // cursor - 1, because cursor points at the closing quote
-        let raw_value = &self.source[self.current_token_start + 1..self.cursor - 1];
-        let length = raw_value.len();
+        let length = self.cursor - 1 - (self.current_token_start + 1);
         Token {
             r#type: TokenType::String,
-            location: (self.current_line, self.current_column - length),
+            location: (self.current_line, self.current_token_column),
+            offset: self.current_token_start + 1,
             length,
-            literal_value: raw_value.to_string(),
+            literal_value: value,
+        }
+    }
+
+    // the literal value is the single character between the quotes, escape sequences applied
+    fn consume_char_literal(&mut self) -> Token {
+        let mut value = String::new();
+
+        loop {
+            if self.is_at_end() {
+                panic!(
+                    "Unterminated character literal, begins at {}:{}",
+                    self.current_line, self.current_token_column
+                );
+            }
+
+            match self.consume_char() {
+                '\'' => break,
+                '\\' if !self.is_at_end() => value.push(self.consume_escape_sequence()),
+                c => value.push(c),
+            }
+        }
+
+        if value.chars().count() != 1 {
+            panic!(
+                "Character literal '{}' has to contain exactly one character at {}:{}",
+                value, self.current_line, self.current_token_column
+            );
+        }
+
+        Token {
+            r#type: TokenType::Char,
+            location: (self.current_line, self.current_token_column),
+            offset: self.current_token_start,
+            length: self.cursor - self.current_token_start,
+            literal_value: value,
+        }
+    }
+
+    // called right after the backslash, shared by strings and character literals
+    fn consume_escape_sequence(&mut self) -> char {
+        let column = self.current_column - 1;
+
+        match self.consume_char() {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            // exactly two hex digits, limited to ascii so the value stays a single byte
+            'x' => {
+                let digits: String = (0..2)
+                    .filter_map(|_| {
+                        let digit = self.peek_char(0).filter(char::is_ascii_hexdigit)?;
+                        self.consume_char();
+                        Some(digit)
+                    })
+                    .collect();
+
+                u8::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|byte| digits.len() == 2 && byte.is_ascii())
+                    .map(char::from)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Malformed escape sequence \\x{} at {}:{}, expected two hex digits up to 7F",
+                            digits, self.current_line, column
+                        )
+                    })
+            }
+            // one to three decimal digits, with the same ascii limit as the hex escape
+            first @ '0'..='9' => {
+                let mut digits = first.to_string();
+                while digits.len() < 3 {
+                    match self.peek_char(0).filter(char::is_ascii_digit) {
+                        Some(digit) => {
+                            self.consume_char();
+                            digits.push(digit);
+                        }
+                        None => break,
+                    }
+                }
+
+                digits
+                    .parse::<u8>()
+                    .ok()
+                    .filter(u8::is_ascii)
+                    .map(char::from)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Malformed escape sequence \\{} at {}:{}, expected a decimal value up to 127",
+                            digits, self.current_line, column
+                        )
+                    })
+            }
+            c => panic!(
+                "Unknown escape sequence \\{} at {}:{}",
+                c, self.current_line, column
+            ),
         }
     }
 
-    // TODO: support floating point numbers
     // TODO: support alternative number formats: hex, binary, etc.
     fn consume_number(&mut self) -> Token {
-        while !self.is_at_end() {
-            let c = self.peek_next_char();
-            if !c.is_digit(10) {
-                break;
+        let mut token_type = TokenType::Number;
+
+        let is_zero = self.source.as_bytes()[self.current_token_start] == b'0';
+        if is_zero && matches!(self.peek_char(0), Some('x' | 'X' | 'b' | 'B')) {
+            // the digits are checked by the parser, which knows the radix
+            while self
+                .peek_char(0)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                self.consume_char();
             }
+
+            return self.make_number_token(token_type);
+        }
+
+        self.consume_digits();
+
+        if self.peek_char(0) == Some('.') {
+            self.consume_char();
+            if !self.peek_char(0).is_some_and(|c| c.is_ascii_digit()) {
+                self.report_malformed_number("expected a digit after the decimal point");
+            }
+            self.consume_digits();
+            token_type = TokenType::Float;
+        }
+
+        if matches!(self.peek_char(0), Some('e' | 'E')) {
             self.consume_char();
+            if matches!(self.peek_char(0), Some('+' | '-')) {
+                self.consume_char();
+            }
+            if !self.peek_char(0).is_some_and(|c| c.is_ascii_digit()) {
+                self.report_malformed_number("expected a digit in the exponent");
+            }
+            self.consume_digits();
+            token_type = TokenType::Float;
         }
 
+        self.make_number_token(token_type)
+    }
+
+    fn make_number_token(&self, token_type: TokenType) -> Token {
         let length = self.cursor - self.current_token_start;
-        let raw_value = &self.source[self.current_token_start..self.cursor];
+        // underscores are only visual separators, so they are dropped from the literal value
+        let literal_value = self.source[self.current_token_start..self.cursor].replace('_', "");
 
         Token {
-            r#type: TokenType::Number,
-            location: (self.current_line, self.current_column - length),
+            r#type: token_type,
+            location: (self.current_line, self.current_token_column),
+            offset: self.current_token_start,
             length,
-            literal_value: raw_value.to_string(),
+            literal_value,
+        }
+    }
+
+    // consumes a run of digits, where every underscore has to be followed by another digit
+    fn consume_digits(&mut self) {
+        while let Some(c) = self.peek_char(0) {
+            if c == '_' {
+                if !self.peek_char(1).is_some_and(|c| c.is_ascii_digit()) {
+                    self.report_malformed_number("expected a digit after the underscore");
+                }
+            } else if !c.is_ascii_digit() {
+                break;
+            }
+            self.consume_char();
         }
     }
 
+    fn report_malformed_number(&self, reason: &str) -> ! {
+        panic!(
+            "Malformed number {} at {}:{}, {}",
+            &self.source[self.current_token_start..self.cursor],
+            self.current_line,
+            self.current_token_column,
+            reason
+        );
+    }
+
     fn consume_identifier_or_keyword(&mut self) -> Token {
         while !self.is_at_end() {
             let c = self.peek_next_char();
 
-            let is_valid = match c {
-                'a'..='z' | 'A'..='Z' | '_' => true,
-                _ => false,
-            };
+            // digits can follow the first character, which makes type names like i64 identifiers
+            let is_valid = matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_');
 
             if !is_valid {
                 break;
@@ -286,7 +598,8 @@ impl Tokenizer {
 
         Token {
             r#type: token_type,
-            location: (self.current_line, self.current_column - length),
+            location: (self.current_line, self.current_token_column),
+            offset: self.current_token_start,
             length,
             literal_value: raw_value.to_string(),
         }
@@ -306,7 +619,12 @@ impl Tokenizer {
             panic!();
         }
 
-        self.source.as_bytes()[self.cursor] as char
+        self.source[self.cursor..].chars().next().unwrap()
+    }
+
+    // the offset counts characters, not bytes
+    fn peek_char(&self, offset: usize) -> Option<char> {
+        self.source[self.cursor..].chars().nth(offset)
     }
 
     fn is_at_end(&self) -> bool {
@@ -323,7 +641,8 @@ impl Tokenizer {
 
         Token {
             r#type: token_type,
-            location: (self.current_line, self.current_column - length),
+            location: (self.current_line, self.current_token_column),
+            offset: self.current_token_start,
             length,
             literal_value,
         }
@@ -336,6 +655,20 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn tokenize_borrowed_and_owned_source_alike() {
+        // given
+        let source = "let x = 1;";
+
+        // when
+        let borrowed = Tokenizer::new(source).tokenize();
+        let owned = Tokenizer::new(source.to_string()).tokenize();
+
+        // then
+        assert_eq!(owned, borrowed);
+        assert_eq!(6, borrowed.len());
+    }
+
     #[test]
     fn tokenize_correctly() {
         // given
@@ -346,8 +679,7 @@ mod test {
         ();
 
 
-"#
-        .to_string();
+"#;
 
         let mut tokenizer = Tokenizer::new(source);
 
@@ -376,8 +708,7 @@ mod test {
         ! "thisisastring()!@#$:: =>"
 
         "another string"
-        "#
-        .to_string();
+        "#;
 
         let mut tokenizer = Tokenizer::new(source);
 
@@ -412,8 +743,7 @@ mod test {
     fn report_error_on_unterminated_string() {
         // given
         let source = r#"! "valid string" "unterminated string !!!
-            "#
-        .to_string();
+            "#;
 
         let mut tokenizer = Tokenizer::new(source);
 
@@ -425,8 +755,7 @@ mod test {
     fn tokenize_numbers() {
         // given
         let source = r#"=
- 1234 5437"#
-            .to_string();
+ 1234 5437"#;
 
         let mut tokenizer = Tokenizer::new(source);
 
@@ -453,10 +782,30 @@ mod test {
         assert_eq!(vec!["1234", "5437"], raw_numbers)
     }
 
+    #[test]
+    fn tokenize_hex_and_binary_numbers() {
+        // given
+        let source = "0xFF 0b1_01 0";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let tokens = tokenizer.tokenize();
+
+        // then
+        let raw_numbers: Vec<&String> = tokens
+            .iter()
+            .filter(|t| t.r#type == TokenType::Number)
+            .map(|t| &t.literal_value)
+            .collect();
+        assert_eq!(vec!["0xFF", "0b101", "0"], raw_numbers);
+        assert_eq!(4, tokens.len());
+    }
+
     #[test]
     fn tokenize_identifier_and_keyword() {
         // given
-        let source = r#"let number=1234;"#.to_string();
+        let source = r#"let number=1234;"#;
 
         let mut tokenizer = Tokenizer::new(source);
 
@@ -480,4 +829,447 @@ mod test {
         let raw_values: Vec<&String> = tokens.iter().map(|t| &t.literal_value).collect();
         assert_eq!(vec!["let", "number", "=", "1234", ";", ""], raw_values)
     }
+
+    #[test]
+    fn tokenize_float_with_underscores_and_exponent() {
+        // given
+        let source = r#"1_000.5e3 2.5E-1 1_000"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let tokens = tokenizer.tokenize();
+
+        // then
+        let token_types: Vec<&TokenType> = tokens.iter().map(|t| &t.r#type).collect();
+        assert_eq!(
+            vec![
+                &TokenType::Float,
+                &TokenType::Float,
+                &TokenType::Number,
+                &TokenType::Eof
+            ],
+            token_types
+        );
+
+        let values: Vec<f64> = tokens[..3]
+            .iter()
+            .map(|t| t.literal_value.parse::<f64>().unwrap())
+            .collect();
+        assert_eq!(vec![1_000_500.0, 0.25, 1000.0], values);
+    }
+
+    #[test]
+    #[should_panic(expected = "Malformed number 1e at 1:0")]
+    fn report_error_on_exponent_without_digits() {
+        // given
+        let source = r#"1e"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    #[should_panic(expected = "Malformed number 1. at 1:0")]
+    fn report_error_on_decimal_point_without_digits() {
+        // given
+        let source = r#"1.e5"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    #[should_panic(expected = "Malformed number 1 at 1:0")]
+    fn report_error_on_underscore_before_decimal_point() {
+        // given
+        let source = r#"1_.5"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    fn list_every_keyword_as_reserved_word() {
+        // given
+        let reserved_words: Vec<&str> = Keyword::all().collect();
+
+        // when
+        let result: Vec<Option<Keyword>> = reserved_words
+            .iter()
+            .map(|word| Keyword::try_match_from_raw_value(word))
+            .collect();
+
+        // then
+        assert_eq!(
+            vec!["let", "const", "as", "print", "true", "false"],
+            reserved_words
+        );
+        assert_eq!(Keyword::ALL.map(Some).to_vec(), result);
+        assert_eq!(None, Keyword::try_match_from_raw_value("print_int"));
+    }
+
+    #[test]
+    fn skip_single_line_block_comment() {
+        // given
+        let source = "let x = /* one */ 1; /* two */";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let tokens = tokenizer.tokenize();
+
+        // then
+        let raw_values: Vec<&String> = tokens.iter().map(|t| &t.literal_value).collect();
+        assert_eq!(vec!["let", "x", "=", "1", ";", ""], raw_values);
+        assert_eq!((1, 18), tokens[3].get_location());
+    }
+
+    #[test]
+    fn count_lines_inside_multi_line_block_comment() {
+        // given
+        let source = "let x = 1;\n/* first\nsecond\nthird */ let y = 2;";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let tokens = tokenizer.tokenize();
+
+        // then
+        assert_eq!(&TokenType::Keyword(Keyword::Let), tokens[5].get_type());
+        assert_eq!(4, tokens[5].get_location().0);
+    }
+
+    #[test]
+    fn skip_nested_block_comments() {
+        // given
+        let source = r#"1 /* outer /* inner */
+still comment */ + 2"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result: Vec<TokenType> = tokenizer
+            .tokenize()
+            .iter()
+            .map(|token| token.get_type().clone())
+            .collect();
+
+        // then
+        assert_eq!(
+            vec![
+                TokenType::Number,
+                TokenType::Plus,
+                TokenType::Number,
+                TokenType::Eof
+            ],
+            result
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unterminated block comment, begins at 1:2")]
+    fn report_unterminated_nested_block_comment_at_outermost_start() {
+        // given
+        let source = r#"1 /* outer /* inner */ no end"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    fn tokenize_whitespace_only_source_to_eof() {
+        // given
+        let source = "  \n \r\n\t  \n";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result = tokenizer.tokenize();
+
+        // then
+        assert_eq!(1, result.len());
+        assert_eq!(&TokenType::Eof, result[0].get_type());
+        assert_eq!(4, result[0].get_location().0);
+    }
+
+    #[test]
+    fn keep_unicode_string_content_unchanged() {
+        // given
+        let source = "print_int(len(\"héllo λ\")); \"é\"";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result = tokenizer.tokenize();
+
+        // then
+        assert_eq!("héllo λ", result[4].get_literal_value());
+        assert_eq!(9, result[4].get_length());
+        assert_eq!("é", result[8].get_literal_value());
+        assert_eq!((1, 27), result[8].get_location());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected character λ at 1:4")]
+    fn report_unicode_character_outside_string() {
+        // given
+        let source = "let λ = 1;";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    fn apply_escape_sequences_in_string() {
+        // given
+        let source = r#""\t\"x\"" "a\\b\n""#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result = tokenizer.tokenize();
+
+        // then
+        assert_eq!("\t\"x\"", result[0].get_literal_value());
+        assert_eq!(7, result[0].get_length());
+        assert_eq!("a\\b\n", result[1].get_literal_value());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown escape sequence \\q at 1:8")]
+    fn report_unknown_escape_sequence() {
+        // given
+        let source = r#"let a "x\q";"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    fn tokenize_char_literals() {
+        // given
+        let source = r"'a' '\n' '\x41' '\''";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result = tokenizer.tokenize();
+
+        // then
+        let values: Vec<(&TokenType, &str)> = result
+            .iter()
+            .map(|token| (token.get_type(), token.get_literal_value()))
+            .collect();
+        assert_eq!(
+            vec![
+                (&TokenType::Char, "a"),
+                (&TokenType::Char, "\n"),
+                (&TokenType::Char, "A"),
+                (&TokenType::Char, "'"),
+                (&TokenType::Eof, "")
+            ],
+            values
+        );
+        assert_eq!((1, 9), result[2].get_location());
+        assert_eq!(6, result[2].get_length());
+    }
+
+    #[test]
+    #[should_panic(expected = "Character literal '' has to contain exactly one character at 1:8")]
+    fn report_empty_char_literal() {
+        // given
+        let source = "let a = '';";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    #[should_panic(expected = "Character literal 'ab' has to contain exactly one character at 1:0")]
+    fn report_char_literal_with_many_characters() {
+        // given
+        let source = "'ab'";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    #[should_panic(expected = "Malformed escape sequence \\x4 at 1:1")]
+    fn report_hex_escape_without_two_digits() {
+        // given
+        let source = r"'\x4'";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    fn apply_decimal_escape_sequences() {
+        // given
+        let source = r#"'\65' '\0' "\9\0101\r""#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result = tokenizer.tokenize();
+
+        // then
+        let values: Vec<&str> = result.iter().map(Token::get_literal_value).collect();
+        assert_eq!(vec!["A", "\0", "\t\n1\r", ""], values);
+    }
+
+    #[test]
+    #[should_panic(expected = "Malformed escape sequence \\128 at 1:1")]
+    fn report_decimal_escape_outside_ascii() {
+        // given
+        let source = r"'\128'";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
+
+    #[test]
+    fn count_columns_from_start_of_line() {
+        // given
+        let source = "let a = 1;\n  print_int(\"x\");";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result = tokenizer.tokenize();
+
+        // then
+        let locations: Vec<(usize, usize)> = result.iter().map(Token::get_location).collect();
+        assert_eq!(
+            vec![
+                (1, 0),
+                (1, 4),
+                (1, 6),
+                (1, 8),
+                (1, 9),
+                (2, 2),
+                (2, 11),
+                (2, 12),
+                (2, 15),
+                (2, 16),
+                (2, 17)
+            ],
+            locations
+        );
+    }
+
+    #[test]
+    fn count_crlf_as_single_line_break() {
+        // given
+        let source = "let a = 1;\r\n/* a\r\ncomment */ let b = 2;\r\n\r\nlet\rc = 3;";
+        let lf_source = source.replace("\r\n", "\n");
+
+        // when
+        let result = Tokenizer::new(source).tokenize();
+        let lf_result = Tokenizer::new(lf_source).tokenize();
+
+        // then
+        let locations: Vec<(usize, usize)> = result.iter().map(Token::get_location).collect();
+        let lf_locations: Vec<(usize, usize)> = lf_result.iter().map(Token::get_location).collect();
+        assert_eq!(lf_locations, locations);
+        assert_eq!((3, 11), result[5].get_location());
+        assert_eq!((5, 0), result[10].get_location());
+        assert_eq!((5, 4), result[11].get_location());
+    }
+
+    #[test]
+    fn end_with_empty_eof_token_after_source() {
+        // given
+        let sources = ["let a = 1;", "let a = 1;\n", "let a = 1;  "];
+
+        // when
+        let result: Vec<Token> = sources
+            .iter()
+            .map(|source| {
+                let tokens = Tokenizer::new(*source).tokenize();
+                tokens.last().unwrap().clone()
+            })
+            .collect();
+
+        // then
+        let locations: Vec<(usize, usize)> = result.iter().map(Token::get_location).collect();
+        let offsets: Vec<usize> = result.iter().map(Token::get_offset).collect();
+        assert!(result
+            .iter()
+            .all(|token| token.get_type() == &TokenType::Eof && token.get_length() == 0));
+        assert_eq!(vec![(1, 10), (2, 0), (1, 12)], locations);
+        assert_eq!(vec![10, 11, 12], offsets);
+    }
+
+    #[test]
+    fn skip_line_comments() {
+        // given
+        let source = "let x = 1; // this is ignored\nlet y = 2; // / * \"\n// only a comment";
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let tokens = tokenizer.tokenize();
+
+        // then
+        let raw_values: Vec<&String> = tokens.iter().map(|t| &t.literal_value).collect();
+        assert_eq!(
+            vec!["let", "x", "=", "1", ";", "let", "y", "=", "2", ";", ""],
+            raw_values
+        );
+        assert_eq!((2, 0), tokens[5].get_location());
+        assert_eq!(3, tokens[10].get_location().0);
+    }
+
+    #[test]
+    fn skip_shebang_on_first_line() {
+        // given
+        let source = r#"#!/usr/bin/env yep
+let a = 1;"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when
+        let result = tokenizer.tokenize();
+
+        // then
+        assert_eq!(&TokenType::Keyword(Keyword::Let), result[0].get_type());
+        assert_eq!((2, 0), result[0].get_location());
+        assert_eq!(6, result.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected character #")]
+    fn report_shebang_after_first_line() {
+        // given
+        let source = r#"let a = 1;
+#!/usr/bin/env yep"#;
+
+        let mut tokenizer = Tokenizer::new(source);
+
+        // when & then
+        tokenizer.tokenize();
+    }
 }
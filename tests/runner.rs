@@ -1,5 +1,6 @@
 use std::fs::File;
 use std::io::Read;
+use std::path::Path;
 use std::process::Command;
 use std::{env, fs};
 
@@ -28,7 +29,7 @@ fn main() {
         // read the expected result
         let result_filename = program.replace(".yep", ".result");
         let mut result_file = File::open(&result_filename)
-            .expect(format!("Cannot open {}, make sure it exists.", result_filename).as_str());
+            .unwrap_or_else(|_| panic!("Cannot open {}, make sure it exists.", result_filename));
 
         let mut expected_contents = String::new();
         result_file.read_to_string(&mut expected_contents).unwrap();
@@ -36,11 +37,17 @@ fn main() {
         let expected_lines: Vec<String> =
             expected_contents.lines().map(|s| s.to_string()).collect();
 
+        // programs ending with a tail expression also describe their expected exit code
+        let exit_code_filename = program.replace(".yep", ".exit_code");
+        let expected_exit_code = fs::read_to_string(&exit_code_filename)
+            .map(|contents| contents.trim().parse::<i32>().unwrap())
+            .unwrap_or(0);
+
         // compile the program
         let yep_output = Command::new("target/release/yep")
-            .arg(format!("{}", program))
+            .arg(&program)
             .arg("-o")
-            .arg("./tests/programs/target/program".to_string())
+            .arg("./tests/programs/target/program")
             .output()
             .expect("failed to execute yep");
         let _stdout = String::from_utf8_lossy(&yep_output.stdout);
@@ -51,6 +58,7 @@ fn main() {
             .output()
             .expect("failed to execute program");
 
+        let exit_code = program_output.status.code();
         let std_output = String::from_utf8(program_output.stdout).unwrap();
 
         let program_lines: Vec<String> = std_output.lines().map(String::from).collect();
@@ -58,15 +66,220 @@ fn main() {
         println!(
             "{}... {}",
             program,
-            if program_lines == expected_lines {
+            if program_lines == expected_lines && exit_code == Some(expected_exit_code) {
                 "OK"
             } else {
                 "FAIL"
             }
         );
         assert_eq!(expected_lines, program_lines);
+        assert_eq!(Some(expected_exit_code), exit_code);
     }
+
+    check_emit_object();
+    check_static_link();
+    check_inline_expression();
+    check_arithmetic_modes();
+    check_front_end_only();
+    check_prebuilt_runtime();
+    check_artifact_paths();
+}
+
+fn check_emit_object() {
+    let object_path = Path::new("./tests/programs/target/emit_obj.o");
+    let program_path = Path::new("./tests/programs/target/emit_obj");
+    fs::create_dir_all("./tests/programs/target").unwrap();
+    let _ = fs::remove_file(object_path);
+    let _ = fs::remove_file(program_path);
+
+    Command::new("target/release/yep")
+        .arg("./tests/programs/print_integer_variable.yep")
+        .arg("--emit-obj")
+        .arg("-o")
+        .arg(object_path)
+        .output()
+        .expect("failed to execute yep");
+
+    let is_object_only = object_path.is_file() && !program_path.exists();
+    println!(
+        "--emit-obj... {}",
+        if is_object_only { "OK" } else { "FAIL" }
+    );
+    assert!(is_object_only);
+}
+
+fn check_static_link() {
+    let program_path = "./tests/programs/target/static_program";
+
+    Command::new("target/release/yep")
+        .arg("./tests/programs/print_integer_variable.yep")
+        .arg("--static")
+        .arg("-o")
+        .arg(program_path)
+        .output()
+        .expect("failed to execute yep");
+
+    // static linkage is hard to check portably, so the binary only has to run correctly
+    let program_output = Command::new(program_path)
+        .output()
+        .expect("failed to execute program");
+    let std_output = String::from_utf8(program_output.stdout).unwrap();
+    let expected_output =
+        fs::read_to_string("./tests/programs/print_integer_variable.result").unwrap();
+
+    let is_correct = std_output.lines().eq(expected_output.lines());
+    println!("--static... {}", if is_correct { "OK" } else { "FAIL" });
+    assert!(is_correct);
+}
+
+fn check_inline_expression() {
+    let program_path = "./tests/programs/target/expr";
+
+    Command::new("target/release/yep")
+        .arg("--expr")
+        .arg("1 + 2")
+        .arg("-o")
+        .arg(program_path)
+        .output()
+        .expect("failed to execute yep");
+
+    // the expression has no semicolon, so its value becomes the exit code
+    let program_output = Command::new(program_path)
+        .output()
+        .expect("failed to execute program");
+
+    let is_correct = program_output.status.code() == Some(3);
+    println!("--expr... {}", if is_correct { "OK" } else { "FAIL" });
+    assert!(is_correct);
 }
+
+fn check_arithmetic_modes() {
+    let program_path = "./tests/programs/target/arith";
+    // x is only known at runtime, so the addition is not folded
+    let exit_code = |mode: &str| {
+        Command::new("target/release/yep")
+            .args(["--expr", "let x = 2147483647; x + 1", "--arith", mode])
+            .arg("-o")
+            .arg(program_path)
+            .output()
+            .expect("failed to execute yep");
+
+        Command::new(program_path)
+            .output()
+            .expect("failed to execute program")
+            .status
+            .code()
+    };
+
+    // the exit code is the low byte of the result, a trap ends the program with a signal
+    let is_correct = exit_code("wrap") == Some(0)
+        && exit_code("saturate") == Some(255)
+        && exit_code("check").is_none();
+    println!("--arith... {}", if is_correct { "OK" } else { "FAIL" });
+    assert!(is_correct);
+}
+
+fn check_front_end_only() {
+    let program_path = Path::new("./tests/programs/target/checked");
+    let bad_source_path = "./tests/programs/target/undeclared_variable.yep";
+    let _ = fs::remove_file(program_path);
+    fs::write(bad_source_path, "let b = a + 1;").unwrap();
+
+    let good_output = Command::new("target/release/yep")
+        .arg("./tests/programs/print_integer_variable.yep")
+        .arg("--check")
+        .arg("-o")
+        .arg(program_path)
+        .output()
+        .expect("failed to execute yep");
+    let bad_output = Command::new("target/release/yep")
+        .arg(bad_source_path)
+        .arg("--check")
+        .output()
+        .expect("failed to execute yep");
+
+    let is_correct = good_output.status.success()
+        && !program_path.exists()
+        && !program_path.with_extension("asm").exists()
+        && !bad_output.status.success()
+        && String::from_utf8_lossy(&bad_output.stderr).contains("Use of undeclared variable a");
+    println!("--check... {}", if is_correct { "OK" } else { "FAIL" });
+    assert!(is_correct);
+}
+
+fn check_prebuilt_runtime() {
+    let runtime_source_path = "./tests/programs/target/prebuilt_runtime.c";
+    let runtime_object_path = "./tests/programs/target/prebuilt_runtime.o";
+    let program_path = "./tests/programs/target/prebuilt_runtime_program";
+    fs::write(
+        runtime_source_path,
+        "#include <stdio.h>\nvoid print_int(int x) { printf(\"prebuilt %d\\n\", x); }\n\
+         void println_int(int x) { print_int(x); }\n",
+    )
+    .unwrap();
+    Command::new("gcc")
+        .args(["-c", runtime_source_path, "-o", runtime_object_path])
+        .output()
+        .expect("failed to execute gcc");
+    let local_runtime_modified = || fs::metadata("runtime.o").and_then(|m| m.modified()).ok();
+    let local_runtime_modified_before = local_runtime_modified();
+
+    Command::new("target/release/yep")
+        .arg("./tests/programs/print_integer_variable.yep")
+        .arg("--runtime-obj")
+        .arg(runtime_object_path)
+        .arg("-o")
+        .arg(program_path)
+        .output()
+        .expect("failed to execute yep");
+
+    let program_output = Command::new(program_path)
+        .output()
+        .expect("failed to execute program");
+    let std_output = String::from_utf8(program_output.stdout).unwrap();
+
+    let is_correct = std_output == "prebuilt 1337\nprebuilt 69420\n"
+        && local_runtime_modified() == local_runtime_modified_before;
+    println!(
+        "--runtime-obj... {}",
+        if is_correct { "OK" } else { "FAIL" }
+    );
+    assert!(is_correct);
+}
+
+fn check_artifact_paths() {
+    let artifacts_directory = Path::new("./tests/programs/target/artifacts");
+    let asm_path = artifacts_directory.join("listing.s");
+    let object_path = artifacts_directory.join("object.obj");
+    let program_path = artifacts_directory.join("binary");
+    let _ = fs::remove_dir_all(artifacts_directory);
+    fs::create_dir_all(artifacts_directory).unwrap();
+
+    Command::new("target/release/yep")
+        .arg("./tests/programs/print_integer_variable.yep")
+        .arg("--asm-out")
+        .arg(&asm_path)
+        .arg("--obj-out")
+        .arg(&object_path)
+        .arg("-o")
+        .arg(&program_path)
+        .output()
+        .expect("failed to execute yep");
+
+    let mut artifacts: Vec<String> = fs::read_dir(artifacts_directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    artifacts.sort();
+
+    let is_correct = artifacts == ["binary", "listing.s", "object.obj"];
+    println!(
+        "--asm-out and --obj-out... {}",
+        if is_correct { "OK" } else { "FAIL" }
+    );
+    assert!(is_correct);
+}
+
 fn get_files_with_extension(directory: &str, extension: &str) -> Vec<String> {
     match fs::read_dir(directory) {
         Ok(entries) => entries
@@ -74,7 +287,7 @@ fn get_files_with_extension(directory: &str, extension: &str) -> Vec<String> {
                 entry.ok().and_then(|dir_entry| {
                     let path = dir_entry.path();
 
-                    if path.is_file() && path.extension().map_or(false, |ext| ext == extension) {
+                    if path.is_file() && path.extension().is_some_and(|ext| ext == extension) {
                         path.to_str().map(|s| s.to_string())
                     } else {
                         None